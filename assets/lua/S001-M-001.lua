@@ -1,15 +1,3 @@
-local ns = "entity_" .. entity.index;
-
-_G[ns] = {
-    id = "S001-M-001",
-    name = "超酷太阳镜",
-    card_type = "Meme",
-    attr = "STAR",
-    race = "NULL",
-    cost = 3,
-    ack = 0,
-};
-
-_G[ns].get_card_info = function(self)
-    return CardInfo(self.id, self.name, self.card_type, self.attr, self.race, self.cost, self.ack);
-end
+-- 超酷太阳镜 卡片数据在 assets/data/cards/S001.ron 中
+-- 装备的人物攻击力 +300 来自卡片数据中的 ack 离开时自动失效 不需要脚本效果
+Card.define {};
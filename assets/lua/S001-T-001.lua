@@ -1,15 +1,8 @@
-local ns = "entity_" .. entity.index;
+local effects = require("effects");
 
-_G[ns] = {
-    id = "S001-T-001",
-    name = "一些微小的工",
-    card_type = "Arcane",
-    attr = "STAR",
-    race = "NULL",
-    cost = 0,
-    ack = 0,
+-- 卡片数据在 assets/data/cards/S001.ron 中 这里只写效果
+Card.define {
+    on_cast = function(self, ctx)
+        effects.destroy_target(self, ctx);
+    end,
 };
-
-_G[ns].get_card_info = function(self)
-    return CardInfo(self.id, self.name, self.card_type, self.attr, self.race, self.cost, self.ack);
-end
@@ -0,0 +1,29 @@
+-- 卡片模板 启动时加载为全局的 Card
+-- 卡片脚本用 Card.define 声明自己的数据和效果 命名空间由模板处理
+-- 没有写的方法使用模板中的 覆盖后可以用 Card.方法名(self, ...) 调用模板的
+local Card = {};
+Card.__index = Card;
+
+-- 声明卡片 返回卡片自己的表 表中的 card 是对局内的卡片编号
+function Card.define(def)
+    local card = setmetatable(def or {}, Card);
+    card.card = card_id(entity.index);
+    _G[card_ns(entity.index)] = card;
+    return card;
+end
+
+-- 写了 info 时覆盖数据库中的卡片信息 字段和 CardInfo 的参数一致
+function Card:get_card_info()
+    local info = self.info;
+    if info == nil then
+        return nil;
+    end
+    return CardInfo(info.id, info.name, info.card_type, info.attr, info.race, info.cost, info.ack, info.placement);
+end
+
+-- 卡片在对局中的信息 和 Game.card 一致
+function Card:state()
+    return Game.card(self.card);
+end
+
+return Card;
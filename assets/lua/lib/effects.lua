@@ -0,0 +1,11 @@
+-- 常用的效果 卡片脚本中 local effects = require("effects")
+local effects = {};
+
+-- 破坏神秘术的目标
+function effects.destroy_target(self, ctx)
+    if ctx.target ~= nil then
+        destroy(ctx.target);
+    end
+end
+
+return effects;
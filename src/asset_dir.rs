@@ -0,0 +1,26 @@
+use std::path::{Path, PathBuf};
+
+/// 资源目录
+/// 启动时直接读取的文件 卡片数据 赛制 布局和 Lua 模块 和 AssetServer 使用同一个资源目录 不依赖工作目录
+
+// AssetPlugin 的资源目录
+pub const ASSET_DIR: &str = "assets";
+
+// 和 AssetPlugin 的规则一致 依次使用 BEVY_ASSET_ROOT CARGO_MANIFEST_DIR 和可执行文件所在的目录
+pub fn asset_root() -> PathBuf {
+    std::env::var_os("BEVY_ASSET_ROOT")
+        .or_else(|| std::env::var_os("CARGO_MANIFEST_DIR"))
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::current_exe()
+                .ok()
+                .and_then(|exe| exe.parent().map(Path::to_path_buf))
+        })
+        .unwrap_or_default()
+        .join(ASSET_DIR)
+}
+
+// 相对资源目录的路径 绝对路径保持不变
+pub fn asset_path(path: impl AsRef<Path>) -> PathBuf {
+    asset_root().join(path)
+}
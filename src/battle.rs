@@ -0,0 +1,156 @@
+use crate::match_state::{CardEntityMap, GameAction, MatchEvent, MatchState};
+use crate::rules::{Action, Location, RuleEvent, ZoneKey};
+use crate::turn::TurnPhase;
+use bevy::prelude::*;
+use bevy_card3d_kit::highlight::Highlight;
+
+/// 战斗
+/// 战斗阶段点击自己战场上的人物 攻击同一列对方战场上的人物
+
+pub const CAN_ATTACK_COLOR: Srgba = bevy::color::palettes::css::ORANGE_RED;
+
+// 宣言攻击
+#[derive(Event, Clone, Debug)]
+pub struct AttackDeclared {
+    pub attacker: Entity,
+    pub defender: Option<Entity>,
+}
+
+// 战斗结算
+#[derive(Event, Clone, Debug)]
+pub struct BattleResolved {
+    pub attacker: Entity,
+    pub defender: Entity,
+    pub winner: Option<Entity>,
+}
+
+// 卡片被破坏
+#[derive(Event, Clone, Debug)]
+pub struct CardDestroyed {
+    pub card: Entity,
+}
+
+// 可以攻击的人物
+#[derive(Component, Clone, Copy, Debug)]
+pub struct CanAttack;
+
+pub struct BattlePlugin;
+
+impl Plugin for BattlePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AttackDeclared>();
+        app.add_event::<BattleResolved>();
+        app.add_event::<CardDestroyed>();
+        app.add_observer(on_click_attack);
+        app.add_systems(OnEnter(TurnPhase::Battle), highlight_attackers);
+        app.add_systems(OnExit(TurnPhase::Battle), clear_attackers);
+        app.add_systems(Update, emit_battle_events);
+    }
+}
+
+// 当前玩家战场上的人物可以攻击
+fn highlight_attackers(
+    mut commands: Commands,
+    match_state: Res<MatchState>,
+    card_entity_map: Res<CardEntityMap>,
+) {
+    let player = match_state.active_player;
+    let Ok(player_state) = match_state.player(player) else {
+        return;
+    };
+    for slot in player_state.slots(ZoneKey::Battle) {
+        let Some(card) = player_state.top(ZoneKey::Battle(slot)) else {
+            continue;
+        };
+        if !match_state.is_actor(card) {
+            continue;
+        }
+        if let Some(entity) = card_entity_map.entity(card) {
+            commands.entity(entity).insert(CanAttack).insert(Highlight {
+                color: CAN_ATTACK_COLOR.into(),
+            });
+        }
+    }
+}
+
+fn clear_attackers(mut commands: Commands, query: Query<Entity, With<CanAttack>>) {
+    for entity in query.iter() {
+        commands
+            .entity(entity)
+            .remove::<CanAttack>()
+            .remove::<Highlight>();
+    }
+}
+
+fn on_click_attack(
+    click: Trigger<Pointer<Click>>,
+    mut commands: Commands,
+    query: Query<(), With<CanAttack>>,
+    match_state: Res<MatchState>,
+    card_entity_map: Res<CardEntityMap>,
+    mut actions: EventWriter<GameAction>,
+) {
+    if query.get(click.target()).is_err() {
+        return;
+    }
+    let Some(card) = card_entity_map.card(click.target()) else {
+        return;
+    };
+    if let Ok(instance) = match_state.card(card) {
+        if let Location::Zone(player, ZoneKey::Battle(_)) = instance.location {
+            actions.write(GameAction(Action::DeclareAttack {
+                player,
+                attacker: card,
+            }));
+            commands
+                .entity(click.target())
+                .remove::<CanAttack>()
+                .remove::<Highlight>();
+        }
+    }
+}
+
+fn emit_battle_events(
+    mut match_events: EventReader<MatchEvent>,
+    card_entity_map: Res<CardEntityMap>,
+    mut attack_declared: EventWriter<AttackDeclared>,
+    mut battle_resolved: EventWriter<BattleResolved>,
+    mut card_destroyed: EventWriter<CardDestroyed>,
+) {
+    for event in match_events.read() {
+        match event.0 {
+            RuleEvent::AttackDeclared {
+                attacker, defender, ..
+            } => {
+                if let Some(attacker) = card_entity_map.entity(attacker) {
+                    attack_declared.write(AttackDeclared {
+                        attacker,
+                        defender: defender.and_then(|c| card_entity_map.entity(c)),
+                    });
+                }
+            }
+            RuleEvent::BattleResolved {
+                attacker,
+                defender,
+                winner,
+            } => {
+                if let (Some(attacker), Some(defender)) = (
+                    card_entity_map.entity(attacker),
+                    card_entity_map.entity(defender),
+                ) {
+                    battle_resolved.write(BattleResolved {
+                        attacker,
+                        defender,
+                        winner: winner.and_then(|c| card_entity_map.entity(c)),
+                    });
+                }
+            }
+            RuleEvent::CardDestroyed { card, .. } => {
+                if let Some(card) = card_entity_map.entity(card) {
+                    card_destroyed.write(CardDestroyed { card });
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -1,7 +1,8 @@
 use bevy::prelude::Reflect;
+use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
-#[derive(Debug, Clone, Reflect, PartialEq)]
+#[derive(Debug, Clone, Reflect, PartialEq, Serialize, Deserialize)]
 pub enum CardType {
     // 人物
     Actor,
@@ -24,7 +25,7 @@ impl FromStr for CardType {
     }
 }
 //属性
-#[derive(Debug, Clone, Reflect)]
+#[derive(Debug, Clone, Reflect, Serialize, Deserialize)]
 pub enum Attr {
     /**
      * 星
@@ -68,7 +69,7 @@ impl FromStr for Attr {
     }
 }
 
-#[derive(Debug, Clone, Reflect)]
+#[derive(Debug, Clone, Reflect, Serialize, Deserialize)]
 pub enum Race {
     // 没有种族
     NULL,
@@ -109,3 +110,32 @@ impl FromStr for Race {
         }
     }
 }
+
+// 登场方式
+#[derive(Debug, Clone, Copy, Reflect, PartialEq, Default, Serialize, Deserialize)]
+pub enum Placement {
+    // 只能放在空的位置
+    #[default]
+    Normal,
+    /**
+     * 牺牲 放在人物上 原来的卡送去墓地
+     */
+    Sacrifice,
+    /**
+     * 洞悉 放在人物上 原来的卡叠放在下面
+     */
+    Insight,
+}
+
+impl FromStr for Placement {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Normal" => Ok(Placement::Normal),
+            "Sacrifice" => Ok(Placement::Sacrifice),
+            "Insight" => Ok(Placement::Insight),
+            _ => Err(()),
+        }
+    }
+}
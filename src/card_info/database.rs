@@ -0,0 +1,113 @@
+use crate::asset_dir::asset_path;
+use crate::card_info::CardInfo;
+use bevy::prelude::*;
+use bevy_card3d_kit::prelude::Card;
+use bevy_scriptum::Script;
+use bevy_scriptum::runtimes::lua::LuaScript;
+use std::collections::HashMap;
+
+/// 卡片数据库
+/// 每个系列一个数据文件 启动时读取 Lua 脚本只负责卡片的效果
+
+// 卡片数据所在的目录 相对 assets
+pub const CARD_DATA_DIR: &str = "data/cards";
+// 卡片脚本所在的目录 相对 assets
+pub const CARD_SCRIPT_DIR: &str = "lua";
+
+// 卡片脚本的路径 相对 assets
+pub fn script_path(id: &str) -> String {
+    format!("{}/{}.lua", CARD_SCRIPT_DIR, id)
+}
+
+#[derive(Resource, Clone, Debug, Default)]
+pub struct CardDatabase {
+    cards: HashMap<String, CardInfo>,
+}
+
+impl CardDatabase {
+    // 读取目录下所有的 ron 文件 出错的文件跳过
+    pub fn load(dir: &str) -> Self {
+        let (database, errors) = Self::read(dir);
+        for err in errors.iter() {
+            warn!("{}", err);
+        }
+        info!("{} cards loaded from {}", database.cards.len(), dir);
+        database
+    }
+
+    // 读取卡片数据 同时返回所有的错误 命令行中使用
+    pub fn read(dir: &str) -> (Self, Vec<String>) {
+        let mut database = Self::default();
+        let mut errors = vec![];
+        let dir = asset_path(dir);
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) => {
+                errors.push(format!(
+                    "failed to read card data {}: {}",
+                    dir.display(),
+                    err
+                ));
+                return (database, errors);
+            }
+        };
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+            .collect();
+        paths.sort();
+        for path in paths {
+            let cards = std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| ron::from_str::<Vec<CardInfo>>(&text).map_err(|e| e.to_string()));
+            match cards {
+                Ok(cards) => {
+                    for card in cards {
+                        if database.cards.contains_key(&card.id) {
+                            errors.push(format!("card {} is defined twice", card.id));
+                        }
+                        database.cards.insert(card.id.clone(), card);
+                    }
+                }
+                Err(err) => errors.push(format!(
+                    "failed to load card data {}: {}",
+                    path.display(),
+                    err
+                )),
+            }
+        }
+        (database, errors)
+    }
+
+    pub fn insert(&mut self, card: CardInfo) {
+        if self.cards.contains_key(&card.id) {
+            warn!("card {} is defined twice", card.id);
+        }
+        self.cards.insert(card.id.clone(), card);
+    }
+
+    pub fn get(&self, id: &str) -> Option<&CardInfo> {
+        self.cards.get(id)
+    }
+
+    // 生成卡片 有效果脚本时同时加载脚本
+    pub fn spawn<'a>(
+        &self,
+        commands: &'a mut Commands,
+        asset_server: &AssetServer,
+        id: &str,
+    ) -> Option<EntityCommands<'a>> {
+        let card_info = self.get(id)?;
+        let mut entity = commands.spawn((
+            Card {
+                origin: Transform::default(),
+            },
+            card_info.clone(),
+        ));
+        let script = script_path(id);
+        if asset_path(&script).exists() {
+            entity.insert(Script::<LuaScript>::new(asset_server.load(script)));
+        }
+        Some(entity)
+    }
+}
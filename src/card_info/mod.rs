@@ -1,11 +1,14 @@
 pub mod card_enums;
+pub mod database;
 
-use crate::card_info::card_enums::{Attr, CardType, Race};
+use crate::card_info::card_enums::{Attr, CardType, Placement, Race};
+use crate::card_info::database::{CARD_DATA_DIR, CardDatabase};
 /// 卡片信息定义
 use bevy::prelude::*;
 use bevy_card3d_kit::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Component, Clone, Reflect, Debug)]
+#[derive(Component, Clone, Reflect, Debug, Serialize, Deserialize)]
 #[reflect(Component)]
 pub struct CardInfo {
     pub id: String,
@@ -15,6 +18,8 @@ pub struct CardInfo {
     pub race: Race,
     pub cost: usize,
     pub ack: u32,
+    #[serde(default)]
+    pub placement: Placement,
 }
 
 impl CardMaterialGetter for CardInfo {
@@ -36,6 +41,7 @@ pub struct CardInfoPlugin;
 impl Plugin for CardInfoPlugin {
     fn build(&self, app: &mut App) {
         app.register_type::<CardInfo>();
+        app.insert_resource(CardDatabase::load(CARD_DATA_DIR));
         bind_card_render::<CardInfo>(app);
     }
 }
@@ -0,0 +1,79 @@
+use crate::match_state::{CardEntityMap, MatchEvent};
+use crate::rules::{Location, RuleEvent};
+use bevy::prelude::*;
+use bevy_card3d_kit::prelude::Moveable;
+
+/// 模因卡作为装备附着在人物上
+/// 附着的卡跟随人物移动 人物离场时由规则核心送去墓地
+
+// 附着在哪张人物卡上
+#[derive(Component, Clone, Debug, Reflect)]
+#[reflect(Component)]
+#[relationship(relationship_target = EquippedMemes)]
+pub struct AttachedTo(pub Entity);
+
+// 人物身上的装备
+#[derive(Component, Clone, Debug, Default, Reflect)]
+#[reflect(Component)]
+#[relationship_target(relationship = AttachedTo)]
+pub struct EquippedMemes(Vec<Entity>);
+
+impl EquippedMemes {
+    pub fn memes(&self) -> &[Entity] {
+        &self.0
+    }
+}
+
+// 装备相对人物的偏移
+const ATTACH_OFFSET: Vec3 = Vec3::new(0.25, -0.35, -0.01);
+
+pub struct AttachPlugin;
+
+impl Plugin for AttachPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<AttachedTo>();
+        app.register_type::<EquippedMemes>();
+        app.add_systems(Update, (sync_attached, follow_host));
+    }
+}
+
+fn sync_attached(
+    mut commands: Commands,
+    mut match_events: EventReader<MatchEvent>,
+    card_entity_map: Res<CardEntityMap>,
+) {
+    for event in match_events.read() {
+        if let RuleEvent::CardMoved { card, from, to } = event.0 {
+            let Some(card_entity) = card_entity_map.entity(card) else {
+                continue;
+            };
+            if let Location::Attached(..) = from {
+                commands.entity(card_entity).remove::<AttachedTo>();
+            }
+            if let Location::Attached(_, host) = to {
+                if let Some(host_entity) = card_entity_map.entity(host) {
+                    commands
+                        .entity(card_entity)
+                        .remove::<Moveable>()
+                        .insert(AttachedTo(host_entity));
+                }
+            }
+        }
+    }
+}
+
+// 装备跟随人物
+fn follow_host(
+    query_host: Query<(&Transform, &EquippedMemes), Without<AttachedTo>>,
+    mut query_meme: Query<&mut Transform, With<AttachedTo>>,
+) {
+    for (host_transform, equipped) in query_host.iter() {
+        for (index, meme) in equipped.memes().iter().enumerate() {
+            if let Ok(mut transform) = query_meme.get_mut(*meme) {
+                transform.translation =
+                    host_transform.translation + ATTACH_OFFSET * (index + 1) as f32;
+                transform.rotation = host_transform.rotation;
+            }
+        }
+    }
+}
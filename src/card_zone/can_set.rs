@@ -1,9 +1,12 @@
 use crate::card_info::CardInfo;
-use crate::card_info::card_enums::CardType;
-use crate::debug_lab::CNA_SET_ON_COLOR;
+use crate::card_info::card_enums::{CardType, Placement};
+use crate::debug_lab::{CAN_ATTACH_COLOR, CAN_REPLACE_COLOR, CNA_SET_ON_COLOR};
 use crate::hand_card::CardLineResource;
-use crate::ui::{EnterEvent, ShowDialogBox, ZoneAndLimit};
-use crate::zone_info::AllZoneInfoResource;
+use crate::match_state::{CardEntityMap, GameAction, MatchEvent, MatchState};
+use crate::rules::cost::CostSolver;
+use crate::rules::{Action, CardId, PlayerId, RuleEvent, ZoneKey};
+use crate::ui::{DialogCallback, EnterEvent, ShowDialogBox, ZoneAndLimit, ZoneCardPair};
+use crate::zone_info::{ZoneInfo, ZoneRegistry, ZoneType};
 use bevy::prelude::*;
 use bevy::render::render_resource::encase::private::RuntimeSizedArray;
 use bevy_card3d_kit::highlight::Highlight;
@@ -30,23 +33,62 @@ impl Plugin for CardSetZonePlugin {
         app.add_observer(on_drag_start);
         app.add_observer(on_drag_end);
         app.add_observer(card_on_zone);
-        // TODO 处理登场效果
+        app.add_event::<CardEntered>();
+        app.add_systems(Update, (resolve_enter_event, emit_card_entered));
+    }
+}
+
+// 卡片登场完成
+#[derive(Event, Clone, Debug)]
+pub struct CardEntered {
+    pub card: Entity,
+    pub zone: Entity,
+}
+
+// 放置的方式
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SetMode {
+    // 放在空的位置
+    Set,
+    // 牺牲和洞悉 放在已有的人物上
+    Replace,
+    // 模因附着在人物上
+    Attach,
+}
+
+fn set_mode(card_info: &CardInfo, zone_info: &ZoneInfo, occupied: bool) -> Option<SetMode> {
+    match (&card_info.card_type, &zone_info.zone_type) {
+        (CardType::Meme, ZoneType::SafeField) => (!occupied).then_some(SetMode::Set),
+        (CardType::Meme, _) => occupied.then_some(SetMode::Attach),
+        (CardType::Actor, _) if !occupied => Some(SetMode::Set),
+        (CardType::Actor, _) => {
+            (card_info.placement != Placement::Normal).then_some(SetMode::Replace)
+        }
+        _ => (!occupied).then_some(SetMode::Set),
     }
 }
 
 fn on_drag_start(
     drag_start: Trigger<Pointer<DragStart>>,
-    query: Query<&CardInfo, With<CanSet>>,
-    query_zone: Query<(Entity, &CanSetOn), Without<CanSet>>,
+    query: Query<(&CardInfo, &PlayerId), With<CanSet>>,
+    query_zone: Query<(Entity, &CanSetOn, &ZoneInfo, &DeskZone, &PlayerId), Without<CanSet>>,
     mut commands: Commands,
 ) {
-    if let Ok(card_info) = query.get(drag_start.target()) {
-        for (entity, can_set_on) in query_zone.iter() {
-            if can_set_on.0.contains(&card_info.card_type) {
-                commands.entity(entity).insert(Highlight {
-                    color: CNA_SET_ON_COLOR.into(),
-                });
+    if let Ok((card_info, player)) = query.get(drag_start.target()) {
+        for (entity, can_set_on, zone_info, desk_zone, owner) in query_zone.iter() {
+            // 只能放到自己的场地上
+            if owner != player || !can_set_on.0.contains(&card_info.card_type) {
+                continue;
             }
+            let color = match set_mode(card_info, zone_info, !desk_zone.card_list.is_empty()) {
+                Some(SetMode::Set) => CNA_SET_ON_COLOR,
+                Some(SetMode::Replace) => CAN_REPLACE_COLOR,
+                Some(SetMode::Attach) => CAN_ATTACH_COLOR,
+                None => continue,
+            };
+            commands.entity(entity).insert(Highlight {
+                color: color.into(),
+            });
         }
     }
 }
@@ -70,12 +112,12 @@ fn on_drag_end(
 fn card_on_zone(
     card_on_zone: Trigger<CardOnZone>,
     mut commands: Commands,
-    query_zone: Query<(&CanSetOn, &DeskZone)>,
+    query_zone: Query<(Option<&CanSetOn>, &ZoneInfo, &DeskZone, Option<&PlayerId>)>,
     query_zone_2: Query<&DeskZone, Without<CanSetOn>>,
-    query_card: Query<&CardInfo, With<CanSet>>,
+    query_card: Query<(&CardInfo, &PlayerId), With<CanSet>>,
     query_line: Query<&CardLine>,
     mut show_dialog: EventWriter<ShowDialogBox<EnterEvent>>,
-    all_zone_info_resource: Res<AllZoneInfoResource>,
+    zone_registry: Res<ZoneRegistry>,
     card_line_resource: Res<CardLineResource>,
 ) {
     // TODO 这里要进行复杂的登场计算
@@ -83,79 +125,205 @@ fn card_on_zone(
     // 需要查看要登场时支付费用的内容
     // 发送要登场的事件
     let card_static = card_on_zone.card.clone();
-    if let Ok(card_info) = query_card.get(card_on_zone.card) {
-        if let Ok((can_set_on, desk_zone)) = query_zone.get(card_on_zone.zone) {
-            //TODO 检查当前费用是否足够!
-            // 对于每个区域要有最小值和最大值！ 但是怎么计算最后选的是否正确呢？
-            if can_set_on.0.contains(&card_info.card_type) {
-                // TODO 不能设置在新的卡片上 但是 牺牲和洞悉两种卡可以！
-                if card_info.card_type == CardType::Actor && desk_zone.card_list.len() > 0 {
-                    return;
-                }
-                let text = format!("Set Card {} ", card_info.name);
-                // 生成文案
-                let text = format!("{} With Cost: {} ", text.clone(), card_info.cost,);
-
-                if let Ok(hand_line) = query_line.get(card_line_resource.my_card_line) {
-                    if let Ok(jq_zone) = query_zone_2.get(all_zone_info_resource.my.jq) {
-                        if let Ok(lx_zone) = query_zone_2.get(all_zone_info_resource.my.lx) {
-                            // 计算限制
-                            // 手卡
-                            let hand_num = if hand_line.card_list.contains(&card_on_zone.card) {
-                                hand_line.card_list.len() - 1
-                            } else {
-                                hand_line.card_list.len()
-                            };
-                            // 费用
-                            let cost = card_info.cost;
-                            let jq = jq_zone.card_list.len();
-                            // lx 剩余
-                            let lx = 6 - lx_zone.card_list.len();
-
-                            let hand_min = if cost <= hand_num.min(lx) {
-                                if cost.min(hand_num.min(lx)) >= jq {
-                                    cost.min(hand_num.min(lx)) - jq
-                                } else {
-                                    0
-                                }
-                            } else {
-                                cost.min(hand_num.min(lx))
-                            };
-                            let hand_max = cost.min(hand_num.min(lx));
-
-                            let jq_min = if cost <= hand_num.min(lx) {
-                                0
-                            } else {
-                                cost - hand_num.min(lx)
-                            };
-                            let jq_max = cost.min(jq);
+    let target_zone = card_on_zone.zone;
+    if let Ok((card_info, player)) = query_card.get(card_on_zone.card) {
+        if let Ok((can_set_on, zone_info, desk_zone, owner)) = query_zone.get(card_on_zone.zone) {
+            let (text, callback): (String, DialogCallback<EnterEvent>) =
+                if card_info.card_type == CardType::Arcane {
+                    // 神秘术可以在任意场地上发动 场上区域最上面的卡作为目标
+                    let target = match zone_info.zone_type {
+                        ZoneType::BattleField
+                        | ZoneType::PreparationField
+                        | ZoneType::SafeField => desk_zone.card_list.last().copied(),
+                        _ => None,
+                    };
+                    // 只能在自己的场地上发动 对方场上有卡时可以作为目标
+                    let Some((zone_owner, zone_key)) = zone_registry.locate(target_zone) else {
+                        return;
+                    };
+                    if zone_owner != *player && !(zone_key.is_field() && target.is_some()) {
+                        return;
+                    }
+                    (
+                        format!("Cast Card {} ", card_info.name),
+                        Arc::new(move |entity, a, b| EnterEvent::CastArcane {
+                            card: entity,
+                            target,
+                            cost_hand: a,
+                            cost_jq: b,
+                        }),
+                    )
+                } else {
+                    if owner != Some(player)
+                        || !can_set_on.is_some_and(|c| c.0.contains(&card_info.card_type))
+                    {
+                        return;
+                    }
+                    // 不能设置在新的卡片上 但是 牺牲和洞悉两种卡可以！ 模因可以附着在人物上
+                    let occupied = desk_zone.card_list.len() > 0;
+                    let text = match set_mode(card_info, zone_info, occupied) {
+                        Some(SetMode::Set) => format!("Set Card {} ", card_info.name),
+                        Some(SetMode::Replace) => {
+                            format!("{:?} Card {} ", card_info.placement, card_info.name)
+                        }
+                        Some(SetMode::Attach) => format!("Attach Card {} ", card_info.name),
+                        None => return,
+                    };
+                    (
+                        text,
+                        Arc::new(move |entity, a, b| EnterEvent::SetCard {
+                            card: entity,
+                            zone: target_zone,
+                            cost_hand: a,
+                            cost_jq: b,
+                        }),
+                    )
+                };
+            // 生成文案
+            let text = format!("{} With Cost: {} ", text.clone(), card_info.cost,);
 
-                            // 这里 还要处理模因卡的问题
-                            show_dialog.write(ShowDialogBox {
-                                card: card_on_zone.card.clone(),
-                                text,
-                                zone_list: vec![ZoneAndLimit {
-                                    entity: all_zone_info_resource.my.jq,
-                                    min: jq_min,
-                                    max: jq_max,
-                                }],
-                                hand_list: vec![ZoneAndLimit {
-                                    entity: card_line_resource.my_card_line,
-                                    min: hand_min,
-                                    max: hand_max,
-                                }],
-                                min: card_info.cost,
-                                max: card_info.cost,
-                                callback: Arc::new(|entity, a, b| EnterEvent::SetCard {
-                                    card: entity,
-                                    cost_hand: a,
-                                    cost_jq: b,
-                                }),
-                            });
+            // 使用卡片拥有者的手卡和场地支付
+            let (Some(jq), Some(lx)) = (
+                zone_registry.zone(*player, ZoneKey::Jq),
+                zone_registry.zone(*player, ZoneKey::Lx),
+            ) else {
+                return;
+            };
+            let card_line = card_line_resource.card_line(*player);
+            if let Ok(hand_line) = query_line.get(card_line) {
+                if let Ok(jq_zone) = query_zone_2.get(jq) {
+                    if let Ok(lx_zone) = query_zone_2.get(lx) {
+                        // 计算限制
+                        // 手卡 不包含要登场的卡
+                        let hand_num = hand_line
+                            .card_list
+                            .iter()
+                            .filter(|e| **e != card_on_zone.card)
+                            .count();
+                        // 从理性区登场时会空出一格
+                        let lx_remain = lx_zone
+                            .opt_capacity
+                            .unwrap_or(usize::MAX)
+                            .saturating_sub(lx_zone.card_list.len())
+                            + usize::from(lx_zone.card_list.contains(&card_on_zone.card));
+                        let solution = CostSolver::new(
+                            card_info.cost,
+                            hand_num,
+                            lx_remain,
+                            jq_zone.card_list.len(),
+                        )
+                        .solve();
+                        if !solution.feasible {
+                            return;
                         }
+
+                        show_dialog.write(ShowDialogBox {
+                            card: card_on_zone.card.clone(),
+                            text,
+                            zone_list: vec![ZoneAndLimit {
+                                entity: jq,
+                                min: solution.jq.min,
+                                max: solution.jq.max,
+                            }],
+                            hand_list: vec![ZoneAndLimit {
+                                entity: card_line,
+                                min: solution.hand.min,
+                                max: solution.hand.max,
+                            }],
+                            min: solution.cost,
+                            max: solution.cost,
+                            callback,
+                        });
                     }
                 }
             }
         }
     }
 }
+
+// 确认登场后交给规则核心结算
+fn resolve_enter_event(
+    mut enter_events: EventReader<EnterEvent>,
+    card_entity_map: Res<CardEntityMap>,
+    zone_registry: Res<ZoneRegistry>,
+    match_state: Res<MatchState>,
+    mut actions: EventWriter<GameAction>,
+) {
+    for event in enter_events.read() {
+        if let EnterEvent::SetCard {
+            card,
+            zone,
+            cost_hand,
+            cost_jq,
+        } = event
+        {
+            let Some(card_id) = card_entity_map.card(*card) else {
+                warn!("card {:?} is not in match", card);
+                continue;
+            };
+            let Some((player, zone_key)) = zone_registry.locate(*zone) else {
+                warn!("zone {:?} is not in match", zone);
+                continue;
+            };
+            actions.write(GameAction(Action::SetCard {
+                player,
+                card: card_id,
+                zone: zone_key,
+                cost_hand: to_card_ids(cost_hand),
+                cost_jq: to_card_ids(cost_jq),
+            }));
+        }
+        if let EnterEvent::CastArcane {
+            card,
+            target,
+            cost_hand,
+            cost_jq,
+        } = event
+        {
+            let Some(card_id) = card_entity_map.card(*card) else {
+                warn!("card {:?} is not in match", card);
+                continue;
+            };
+            let Some(player) = match_state.card(card_id).ok().map(|c| c.owner) else {
+                continue;
+            };
+            actions.write(GameAction(Action::CastArcane {
+                player,
+                card: card_id,
+                target: target.and_then(|entity| card_entity_map.card(entity)),
+                cost_hand: to_card_ids(cost_hand),
+                cost_jq: to_card_ids(cost_jq),
+            }));
+        }
+    }
+}
+
+fn to_card_ids(pairs: &[ZoneCardPair]) -> Vec<CardId> {
+    pairs.iter().map(|(_, card)| card.0).collect()
+}
+
+fn emit_card_entered(
+    mut commands: Commands,
+    mut match_events: EventReader<MatchEvent>,
+    card_entity_map: Res<CardEntityMap>,
+    zone_registry: Res<ZoneRegistry>,
+    mut card_entered: EventWriter<CardEntered>,
+) {
+    for event in match_events.read() {
+        if let RuleEvent::CardEntered { player, card, zone } = event.0 {
+            if let (Some(card_entity), Some(zone_entity)) = (
+                card_entity_map.entity(card),
+                zone_registry.zone(player, zone),
+            ) {
+                commands
+                    .entity(card_entity)
+                    .remove::<CanSet>()
+                    .remove::<Highlight>();
+                card_entered.write(CardEntered {
+                    card: card_entity,
+                    zone: zone_entity,
+                });
+            }
+        }
+    }
+}
@@ -1,20 +1,26 @@
 use crate::card_info::CardInfo;
-use crate::card_info::card_enums::CardType;
 use crate::card_zone::can_set::CanSet;
+use crate::deck::LoadDeck;
 use crate::hand_card::CardLineResource;
-use crate::zone_info::AllZoneInfoResource;
+use crate::match_state::{GameAction, MatchState};
+use crate::rules::cost::CostSolver;
+use crate::rules::{Action, PlayerId, ZoneKey};
+use crate::snapshot::{DEFAULT_SAVE, LoadMatch, SaveMatch};
+use crate::zone_info::ZoneRegistry;
 use bevy::ecs::relationship::RelatedSpawnerCommands;
 use bevy::ecs::system::IntoObserverSystem;
 use bevy::prelude::*;
 use bevy_card3d_kit::highlight::Highlight;
-use bevy_card3d_kit::prelude::card_state::{CardState, ChangeCardState};
-use bevy_card3d_kit::prelude::{Card, CardLine, HandCard, HandCardChanged, Moveable};
-use bevy_card3d_kit::zone::desk_zone::{DeskCard, DeskZone, DeskZoneChangedEvent};
-use bevy_scriptum::Script;
-use bevy_scriptum::runtimes::lua::LuaScript;
+use bevy_card3d_kit::prelude::CardLine;
+use bevy_card3d_kit::zone::desk_zone::DeskZone;
 
 pub const CAN_SET_COLOR: Srgba = bevy::color::palettes::css::LIGHT_SKY_BLUE;
 pub const CNA_SET_ON_COLOR: Srgba = bevy::color::palettes::css::DARK_RED;
+pub const CAN_REPLACE_COLOR: Srgba = bevy::color::palettes::css::DARK_ORANGE;
+pub const CAN_ATTACH_COLOR: Srgba = bevy::color::palettes::css::MEDIUM_PURPLE;
+
+// 测试用的牌组
+const STARTER_DECK: &str = "decks/starter.deck.ron";
 
 pub struct DebugLabPlugin;
 
@@ -57,12 +63,16 @@ fn setup(mut commands: Commands) {
                     spawn_button(parent, "highlight".to_string(), on_click_highlight);
                     spawn_button(parent, "to_lx".to_string(), put_hand_in_lx);
                     spawn_button(parent, "lx_change".to_string(), change_all_lx);
-                    spawn_button(parent, "to_jq".to_string(), put_desk_in_jq)
+                    spawn_button(parent, "to_jq".to_string(), put_desk_in_jq);
+                    spawn_button(parent, "start".to_string(), on_click_start);
+                    spawn_button(parent, "next_phase".to_string(), on_click_next_phase);
+                    spawn_button(parent, "save".to_string(), on_click_save);
+                    spawn_button(parent, "load".to_string(), on_click_load)
                 });
         });
 }
 
-fn spawn_button<E: Event, B: Bundle, M>(
+pub fn spawn_button<E: Event, B: Bundle, M>(
     mut parent: &mut RelatedSpawnerCommands<ChildOf>,
     text: String,
     observer: impl IntoObserverSystem<E, B, M>,
@@ -98,85 +108,69 @@ fn spawn_button<E: Event, B: Bundle, M>(
 
 fn on_click_init_desk(
     _click: Trigger<Pointer<Click>>,
-    mut commands: Commands,
-    all_zone_info_resource: Res<AllZoneInfoResource>,
     asset_server: Res<AssetServer>,
+    mut load_deck: EventWriter<LoadDeck>,
 ) {
     info!("Clicked on pointer");
-    // 这里是测试的卡片的代码
-    for card_num in "S001-A-001,EX001-A-002,NAAI-A-001,S001-T-001,S001-M-001".split(",") {
-        commands.spawn((
-            Script::<LuaScript>::new(asset_server.load(format!("lua/{}.lua", card_num))),
-            Card {
-                origin: Transform::default(),
-            },
-            CardState {
-                face_up: false,
-                vertical: true,
-            },
-            DeskCard {
-                belongs_to_desk: Some(all_zone_info_resource.my.desk.clone()),
-            },
-        ));
+    // 这里是测试的牌组 双方使用同一套
+    let deck = asset_server.load(STARTER_DECK);
+    for player in PlayerId::ALL {
+        load_deck.write(LoadDeck {
+            player,
+            deck: deck.clone(),
+        });
     }
 }
 
+// 对局中只能在当前玩家的抽卡阶段抽卡
 fn on_click_draw(
     _click: Trigger<Pointer<Click>>,
-    mut commands: Commands,
-    all_zone_info_resource: Res<AllZoneInfoResource>,
-    card_line_resource: Res<CardLineResource>,
-    mut query_desks: Query<&mut DeskZone>,
-    mut desk_card_event: EventWriter<DeskZoneChangedEvent>,
+    match_state: Res<MatchState>,
+    mut actions: EventWriter<GameAction>,
 ) {
-    if let Ok(mut desk_zone) = query_desks.get_mut(all_zone_info_resource.my.desk) {
-        if let Some(card_entity) = desk_zone.card_list.pop() {
-            commands
-                .entity(card_entity)
-                .insert(HandCard {
-                    belong_to_card_line: Some(card_line_resource.my_card_line),
-                })
-                .insert(Moveable);
-            desk_card_event.write(DeskZoneChangedEvent::Removed {
-                desk: all_zone_info_resource.my.desk,
-                card: card_entity,
-            });
-        }
-    }
+    actions.write(GameAction(Action::Draw {
+        player: match_state.active_player,
+    }));
 }
 
 fn on_click_highlight(
     _click: Trigger<Pointer<Click>>,
     mut commands: Commands,
-    all_zone_info_resource: Res<AllZoneInfoResource>,
+    zone_registry: Res<ZoneRegistry>,
     card_line_resource: Res<CardLineResource>,
     query_card_line: Query<&CardLine>,
     query_desks: Query<&DeskZone>,
     query_cards: Query<&CardInfo>,
 ) {
-    if let Ok(lx_zone) = query_desks.get(all_zone_info_resource.my.lx) {
-        if let Ok(jq_zone) = query_desks.get(all_zone_info_resource.my.jq) {
+    let (Some(lx_entity), Some(jq_entity)) = (
+        zone_registry.zone(PlayerId::MY, ZoneKey::Lx),
+        zone_registry.zone(PlayerId::MY, ZoneKey::Jq),
+    ) else {
+        return;
+    };
+    if let Ok(lx_zone) = query_desks.get(lx_entity) {
+        if let Ok(jq_zone) = query_desks.get(jq_entity) {
             let jq = jq_zone.card_list.len();
-            let lx_used = lx_zone.card_list.len();
-            let lx_remain = 6 - lx_used;
-            if let Ok(card_line) = query_card_line.get(card_line_resource.my_card_line) {
+            // 理性区的容量来自棋盘布局
+            let lx_capacity = lx_zone.opt_capacity.unwrap_or(usize::MAX);
+            let lx_remain = lx_capacity.saturating_sub(lx_zone.card_list.len());
+            if let Ok(card_line) = query_card_line.get(card_line_resource.card_line(PlayerId::MY)) {
                 let hand_num = card_line.card_list.len();
-                if hand_num > 0 {
-                    //FIX手牌为0
-                    match_can_set(
-                        &card_line.card_list,
-                        &mut commands,
-                        hand_num - 1,
-                        lx_remain,
-                        jq,
-                        query_cards,
-                    );
-                }
+                // 手卡登场时自己不能作为费用
+                match_can_set(
+                    &card_line.card_list,
+                    &mut commands,
+                    hand_num.saturating_sub(1),
+                    lx_remain,
+                    jq,
+                    query_cards,
+                );
+                // 理性区登场时会空出一格
                 match_can_set(
                     &lx_zone.card_list,
                     &mut commands,
                     hand_num,
-                    lx_remain,
+                    (lx_remain + 1).min(lx_capacity),
                     jq,
                     query_cards,
                 );
@@ -197,22 +191,18 @@ fn match_can_set(
         if let Ok(card_info) = query_cards.get(*card_entity) {
             // 每次刷新状态
             commands.entity(*card_entity).remove::<Highlight>();
-            match card_info.card_type {
-                CardType::Arcane => {
-                    // do nothing
-                }
-                // 费用 <= min(hand_num,lx_remain)+jq 小于手牌和剩余槽位小的那个 加上 激情
-                _ => {
-                    if card_info.cost <= (hand_num.min(lx_remain) + jq) {
-                        // 这样的卡才能设置！
-                        commands
-                            .entity(*card_entity)
-                            .insert(CanSet)
-                            .insert(Highlight {
-                                color: CAN_SET_COLOR.into(),
-                            });
-                    }
-                }
+            // 神秘术和其他卡一样 费用足够时可以发动
+            if CostSolver::new(card_info.cost, hand_num, lx_remain, jq)
+                .solve()
+                .feasible
+            {
+                // 这样的卡才能设置！
+                commands
+                    .entity(*card_entity)
+                    .insert(CanSet)
+                    .insert(Highlight {
+                        color: CAN_SET_COLOR.into(),
+                    });
             }
         }
     }
@@ -220,103 +210,56 @@ fn match_can_set(
 
 fn put_hand_in_lx(
     _click: Trigger<Pointer<Click>>,
-    mut commands: Commands,
-    all_zone_info_resource: Res<AllZoneInfoResource>,
-    card_line_resource: Res<CardLineResource>,
-    mut query_card_line: Query<&mut CardLine>,
-    mut hand_card_event: EventWriter<HandCardChanged>,
-    query_desks: Query<&DeskZone>,
+    match_state: Res<MatchState>,
+    mut actions: EventWriter<GameAction>,
 ) {
-    if let Ok(mut card_line) = query_card_line.get_mut(card_line_resource.my_card_line) {
-        if let Ok(lx_zone) = query_desks.get(all_zone_info_resource.my.lx) {
-            if card_line.card_list.len() > 0 && lx_zone.card_list.len() < 6 {
-                if let Some(card_entity) = card_line.card_list.pop() {
-                    hand_card_event.write(HandCardChanged::Remove {
-                        card_entity,
-                        card_line_entity: card_line_resource.my_card_line,
-                    });
-                    commands
-                        .entity(card_entity)
-                        .remove::<HandCard>()
-                        // FIXME: 此处移动层面不一样
-                        // .remove::<Moveable>()
-                        .insert(DeskCard {
-                            belongs_to_desk: Some(all_zone_info_resource.my.lx),
-                        });
-                }
-            }
+    if let Ok(player) = match_state.player(PlayerId::MY) {
+        if let Some(card) = player.hand.last() {
+            actions.write(GameAction(Action::PutHandInLx {
+                player: PlayerId::MY,
+                card: *card,
+            }));
         }
     }
 }
 
-fn put_desk_in_jq(
-    _click: Trigger<Pointer<Click>>,
-    mut commands: Commands,
-    all_zone_info_resource: Res<AllZoneInfoResource>,
-    mut desk_card_event: EventWriter<DeskZoneChangedEvent>,
-    mut query_desks: Query<&mut DeskZone>,
-) {
-    if let Ok(jq_zone) = query_desks.get(all_zone_info_resource.my.jq) {
-        if jq_zone.card_list.len() < 6 {
-            // 从卡组里进行添加
-            if let Ok(mut desk_zone) = query_desks.get_mut(all_zone_info_resource.my.desk) {
-                if desk_zone.card_list.len() > 0 {
-                    if let Some(card_entity) = desk_zone.card_list.pop() {
-                        commands
-                            .entity(card_entity)
-                            .remove::<Highlight>()
-                            .remove::<DeskCard>()
-                            .remove::<CardState>()
-                            .insert(DeskCard {
-                                belongs_to_desk: Some(all_zone_info_resource.my.jq),
-                            });
-                        // 去除卡组
-                        desk_card_event.write(DeskZoneChangedEvent::Removed {
-                            desk: all_zone_info_resource.my.desk,
-                            card: card_entity,
-                        });
-                    }
-                }
-            }
-        }
-    }
+fn put_desk_in_jq(_click: Trigger<Pointer<Click>>, mut actions: EventWriter<GameAction>) {
+    actions.write(GameAction(Action::PutDeskInJq {
+        player: PlayerId::MY,
+    }));
 }
 
-fn change_all_lx(
+fn change_all_lx(_click: Trigger<Pointer<Click>>, mut actions: EventWriter<GameAction>) {
+    // 所有竖直的回到手卡 所有横置的竖直
+    actions.write(GameAction(Action::RecoverLx {
+        player: PlayerId::MY,
+    }));
+}
+
+fn on_click_start(_click: Trigger<Pointer<Click>>, mut actions: EventWriter<GameAction>) {
+    actions.write(GameAction(Action::StartMatch {
+        first: PlayerId::MY,
+    }));
+}
+
+fn on_click_next_phase(
     _click: Trigger<Pointer<Click>>,
-    mut commands: Commands,
-    all_zone_info_resource: Res<AllZoneInfoResource>,
-    card_line_resource: Res<CardLineResource>,
-    mut _query_card_line: Query<&mut CardLine>,
-    query_desks: Query<&DeskZone>,
-    query_cards: Query<&CardState, With<Card>>,
-    mut desk_card_event: EventWriter<DeskZoneChangedEvent>,
+    match_state: Res<MatchState>,
+    mut actions: EventWriter<GameAction>,
 ) {
-    //TODO 所有竖直的回到手卡
+    actions.write(GameAction(Action::AdvancePhase {
+        player: match_state.active_player,
+    }));
+}
 
-    // 所有理性区的卡竖直
-    if let Ok(lx_zone) = query_desks.get(all_zone_info_resource.my.lx) {
-        if lx_zone.card_list.len() > 0 {
-            for entity in lx_zone.card_list.iter() {
-                if let Ok(card_state) = query_cards.get(*entity) {
-                    if card_state.vertical {
-                        // 去除卡组
-                        desk_card_event.write(DeskZoneChangedEvent::Removed {
-                            desk: all_zone_info_resource.my.lx,
-                            card: *entity,
-                        });
-                        // 加入手卡
-                        commands.entity(*entity).insert(HandCard {
-                            belong_to_card_line: Some(card_line_resource.my_card_line),
-                        });
-                    } else {
-                        commands.entity(*entity).insert(ChangeCardState(CardState {
-                            face_up: true,
-                            vertical: true,
-                        }));
-                    }
-                }
-            }
-        }
-    }
+fn on_click_save(_click: Trigger<Pointer<Click>>, mut save_match: EventWriter<SaveMatch>) {
+    save_match.write(SaveMatch {
+        path: DEFAULT_SAVE.to_string(),
+    });
+}
+
+fn on_click_load(_click: Trigger<Pointer<Click>>, mut load_match: EventWriter<LoadMatch>) {
+    load_match.write(LoadMatch {
+        path: DEFAULT_SAVE.to_string(),
+    });
 }
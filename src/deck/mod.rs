@@ -0,0 +1,237 @@
+use crate::card_info::database::CardDatabase;
+use crate::deck::validate::{MatchFormat, Violation};
+use crate::match_state::{CardInstanceId, MatchState};
+use crate::rules::{Location, PlayerId, ZoneKey};
+use crate::zone_info::ZoneRegistry;
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext, LoadState};
+use bevy::prelude::*;
+use bevy_card3d_kit::prelude::card_state::CardState;
+use bevy_card3d_kit::zone::desk_zone::DeskCard;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+pub mod validate;
+
+/// 牌组
+/// 牌组文件 *.deck.ron 作为资源加载 通过 LoadDeck 放入玩家的卡组
+
+// 牌组中的一种卡
+#[derive(Clone, Debug, Deserialize)]
+pub struct DeckEntry {
+    pub id: String,
+    #[serde(default = "default_count")]
+    pub count: usize,
+}
+
+fn default_count() -> usize {
+    1
+}
+
+#[derive(Asset, TypePath, Clone, Debug, Deserialize)]
+pub struct DeckList {
+    pub name: String,
+    // 牌组的作者
+    #[serde(default)]
+    pub player: Option<String>,
+    pub cards: Vec<DeckEntry>,
+    // 备牌 不会放入卡组
+    #[serde(default)]
+    pub sideboard: Vec<DeckEntry>,
+}
+
+impl DeckList {
+    // 展开成卡片编号 按牌组中的顺序
+    pub fn card_ids(&self) -> Vec<&str> {
+        self.cards
+            .iter()
+            .flat_map(|entry| std::iter::repeat_n(entry.id.as_str(), entry.count))
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub enum DeckError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    // 不符合赛制
+    Illegal(Vec<Violation>),
+}
+
+impl std::fmt::Display for DeckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeckError::Io(err) => write!(f, "failed to read deck: {}", err),
+            DeckError::Parse(err) => write!(f, "failed to parse deck: {}", err),
+            DeckError::Illegal(violations) => {
+                write!(f, "illegal deck:")?;
+                for violation in violations.iter() {
+                    write!(f, " {};", violation)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for DeckError {}
+
+impl From<std::io::Error> for DeckError {
+    fn from(err: std::io::Error) -> Self {
+        DeckError::Io(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for DeckError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        DeckError::Parse(err)
+    }
+}
+
+#[derive(Default)]
+pub struct DeckListLoader;
+
+impl AssetLoader for DeckListLoader {
+    type Asset = DeckList;
+    type Settings = ();
+    type Error = DeckError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["deck.ron"]
+    }
+}
+
+// 把牌组放入玩家的卡组
+#[derive(Event, Clone, Debug)]
+pub struct LoadDeck {
+    pub player: PlayerId,
+    pub deck: Handle<DeckList>,
+}
+
+// 牌组放入卡组完成
+#[derive(Event, Clone, Debug)]
+pub struct DeckLoaded {
+    pub player: PlayerId,
+    pub deck: Handle<DeckList>,
+    pub count: usize,
+}
+
+// 不合法的牌组 存在时不能开始对局
+#[derive(Resource, Clone, Debug, Default)]
+pub struct DeckLegality {
+    pub illegal: HashMap<PlayerId, Vec<Violation>>,
+}
+
+impl DeckLegality {
+    pub fn can_start(&self) -> bool {
+        self.illegal.is_empty()
+    }
+}
+
+pub struct DeckPlugin;
+
+impl Plugin for DeckPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(MatchFormat::load());
+        app.init_resource::<DeckLegality>();
+        app.init_asset::<DeckList>();
+        app.init_asset_loader::<DeckListLoader>();
+        app.add_event::<LoadDeck>();
+        app.add_event::<DeckLoaded>();
+        app.add_systems(Update, load_decks);
+    }
+}
+
+fn load_decks(
+    mut commands: Commands,
+    mut load_decks: EventReader<LoadDeck>,
+    // 等待资源加载的牌组
+    mut pending: Local<Vec<LoadDeck>>,
+    decks: Res<Assets<DeckList>>,
+    asset_server: Res<AssetServer>,
+    card_database: Res<CardDatabase>,
+    match_format: Res<MatchFormat>,
+    mut deck_legality: ResMut<DeckLegality>,
+    zone_registry: Res<ZoneRegistry>,
+    mut match_state: ResMut<MatchState>,
+    mut deck_loaded: EventWriter<DeckLoaded>,
+) {
+    pending.extend(load_decks.read().cloned());
+    let mut waiting = vec![];
+    for load_deck in pending.drain(..) {
+        let Some(deck) = decks.get(&load_deck.deck) else {
+            if let Some(LoadState::Failed(err)) = asset_server.get_load_state(&load_deck.deck) {
+                error!("deck for {:?} failed to load: {}", load_deck.player, err);
+            } else {
+                waiting.push(load_deck);
+            }
+            continue;
+        };
+        let player = load_deck.player;
+        // 不合法的牌组不会放入卡组
+        let violations = match_format.0.validate(deck, &card_database);
+        if !violations.is_empty() {
+            error!(
+                "deck {} for {:?} in {}: {}",
+                deck.name,
+                player,
+                match_format.0.name,
+                DeckError::Illegal(violations.clone())
+            );
+            deck_legality.illegal.insert(player, violations);
+            continue;
+        }
+        deck_legality.illegal.remove(&player);
+        let mut count = 0;
+        for id in deck.card_ids() {
+            // 先生成实体 生成成功后再放入规则核心 保证两边的卡一致
+            let Some(mut entity) = card_database.spawn(&mut commands, &asset_server, id) else {
+                continue;
+            };
+            let Ok(card) = match_state.add_card(
+                player,
+                card_database.get(id).cloned(),
+                Location::Zone(player, ZoneKey::Desk),
+            ) else {
+                entity.despawn();
+                continue;
+            };
+            entity.insert((
+                CardState {
+                    face_up: false,
+                    vertical: true,
+                },
+                DeskCard {
+                    belongs_to_desk: zone_registry.zone(player, ZoneKey::Desk),
+                },
+                player,
+                CardInstanceId(card),
+            ));
+            count += 1;
+        }
+        info!(
+            "deck {} by {} loaded for {:?}: {} cards",
+            deck.name,
+            deck.player.as_deref().unwrap_or("unknown"),
+            player,
+            count
+        );
+        deck_loaded.write(DeckLoaded {
+            player,
+            deck: load_deck.deck.clone(),
+            count,
+        });
+    }
+    *pending = waiting;
+}
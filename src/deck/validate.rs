@@ -0,0 +1,438 @@
+use crate::asset_dir::asset_path;
+use crate::card_info::card_enums::CardType;
+use crate::card_info::database::{CARD_DATA_DIR, CardDatabase};
+use crate::deck::DeckList;
+use bevy::prelude::*;
+use serde::Deserialize;
+
+/// 牌组合法性检查
+/// 赛制文件放在 assets/data/formats 下 每个赛制一个文件
+
+// 相对 assets
+pub const FORMAT_DIR: &str = "data/formats";
+pub const DEFAULT_FORMAT: &str = "debug";
+
+// 某种类型的卡的数量限制
+#[derive(Clone, Debug, Deserialize)]
+pub struct TypeQuota {
+    pub card_type: CardType,
+    pub min: usize,
+    pub max: usize,
+}
+
+// 限制数量的卡
+#[derive(Clone, Debug, Deserialize)]
+pub struct LimitedCard {
+    pub id: String,
+    pub max: usize,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct DeckFormat {
+    pub name: String,
+    pub min_size: usize,
+    pub max_size: usize,
+    // 同名卡的数量上限
+    pub max_copies: usize,
+    #[serde(default)]
+    pub type_quotas: Vec<TypeQuota>,
+    #[serde(default)]
+    pub banned: Vec<String>,
+    #[serde(default)]
+    pub limited: Vec<LimitedCard>,
+    // 可以使用的系列 为空时不限
+    #[serde(default)]
+    pub sets: Vec<String>,
+}
+
+// 不合法的地方
+#[derive(Clone, Debug, PartialEq)]
+pub enum Violation {
+    UnknownCard(String),
+    TooFewCards {
+        count: usize,
+        min: usize,
+    },
+    TooManyCards {
+        count: usize,
+        max: usize,
+    },
+    TooManyCopies {
+        id: String,
+        count: usize,
+        max: usize,
+    },
+    Banned(String),
+    TypeQuota {
+        card_type: CardType,
+        count: usize,
+        min: usize,
+        max: usize,
+    },
+    // 赛制中不能使用的系列
+    IllegalSet {
+        id: String,
+        set: String,
+    },
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Violation::UnknownCard(id) => write!(f, "unknown card {}", id),
+            Violation::TooFewCards { count, min } => {
+                write!(f, "deck has {} cards, at least {} needed", count, min)
+            }
+            Violation::TooManyCards { count, max } => {
+                write!(f, "deck has {} cards, at most {} allowed", count, max)
+            }
+            Violation::TooManyCopies { id, count, max } => {
+                write!(f, "{} copies of {}, at most {} allowed", count, id, max)
+            }
+            Violation::Banned(id) => write!(f, "{} is banned", id),
+            Violation::TypeQuota {
+                card_type,
+                count,
+                min,
+                max,
+            } => write!(
+                f,
+                "{} {:?} cards, {}..={} allowed",
+                count, card_type, min, max
+            ),
+            Violation::IllegalSet { id, set } => write!(f, "{} is from illegal set {}", id, set),
+        }
+    }
+}
+
+// 卡片编号的系列 S001-A-001 => S001
+pub fn set_of(id: &str) -> &str {
+    id.split('-').next().unwrap_or(id)
+}
+
+impl DeckFormat {
+    pub fn load(name: &str) -> Result<Self, String> {
+        let path = asset_path(FORMAT_DIR).join(format!("{}.ron", name));
+        let text =
+            std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        ron::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // 检查牌组 备牌也要满足同名卡和禁卡的限制
+    pub fn validate(&self, deck: &DeckList, card_database: &CardDatabase) -> Vec<Violation> {
+        let mut violations = vec![];
+        let size: usize = deck.cards.iter().map(|entry| entry.count).sum();
+        if size < self.min_size {
+            violations.push(Violation::TooFewCards {
+                count: size,
+                min: self.min_size,
+            });
+        }
+        if size > self.max_size {
+            violations.push(Violation::TooManyCards {
+                count: size,
+                max: self.max_size,
+            });
+        }
+
+        // 同名卡的数量 按第一次出现的顺序
+        let mut copies: Vec<(&str, usize)> = vec![];
+        for entry in deck.cards.iter().chain(deck.sideboard.iter()) {
+            match copies.iter_mut().find(|(id, _)| *id == entry.id) {
+                Some((_, count)) => *count += entry.count,
+                None => copies.push((entry.id.as_str(), entry.count)),
+            }
+        }
+        for (id, count) in copies.iter() {
+            if card_database.get(id).is_none() {
+                violations.push(Violation::UnknownCard(id.to_string()));
+            }
+            if self.banned.iter().any(|banned| banned == id) {
+                violations.push(Violation::Banned(id.to_string()));
+                continue;
+            }
+            let max = self
+                .limited
+                .iter()
+                .find(|limited| limited.id == *id)
+                .map(|limited| limited.max)
+                .unwrap_or(self.max_copies);
+            if *count > max {
+                violations.push(Violation::TooManyCopies {
+                    id: id.to_string(),
+                    count: *count,
+                    max,
+                });
+            }
+            let set = set_of(id);
+            if !self.sets.is_empty() && !self.sets.iter().any(|s| s == set) {
+                violations.push(Violation::IllegalSet {
+                    id: id.to_string(),
+                    set: set.to_string(),
+                });
+            }
+        }
+
+        for quota in self.type_quotas.iter() {
+            let count: usize = deck
+                .cards
+                .iter()
+                .filter(|entry| {
+                    card_database
+                        .get(&entry.id)
+                        .is_some_and(|info| info.card_type == quota.card_type)
+                })
+                .map(|entry| entry.count)
+                .sum();
+            if count < quota.min || count > quota.max {
+                violations.push(Violation::TypeQuota {
+                    card_type: quota.card_type.clone(),
+                    count,
+                    min: quota.min,
+                    max: quota.max,
+                });
+            }
+        }
+        violations
+    }
+}
+
+// 对局使用的赛制
+#[derive(Resource, Clone, Debug)]
+pub struct MatchFormat(pub DeckFormat);
+
+impl MatchFormat {
+    // 环境变量 DECK_FORMAT 指定赛制
+    pub fn load() -> Self {
+        let name = std::env::var("DECK_FORMAT").unwrap_or_else(|_| DEFAULT_FORMAT.to_string());
+        match DeckFormat::load(&name) {
+            Ok(format) => Self(format),
+            Err(err) => {
+                error!(
+                    "failed to load deck format {}: {}, decks are not validated",
+                    name, err
+                );
+                // 读取失败时不限制
+                Self(DeckFormat {
+                    name,
+                    min_size: 0,
+                    max_size: usize::MAX,
+                    max_copies: usize::MAX,
+                    type_quotas: vec![],
+                    banned: vec![],
+                    limited: vec![],
+                    sets: vec![],
+                })
+            }
+        }
+    }
+}
+
+// 命令行检查牌组
+// cargo run -- validate-deck assets/decks/starter.deck.ron [format]
+pub fn run_cli(args: &[String]) -> i32 {
+    let Some(path) = args.first() else {
+        eprintln!("usage: validate-deck <deck file> [format]");
+        return 2;
+    };
+    let format_name = args.get(1).map(String::as_str).unwrap_or(DEFAULT_FORMAT);
+    let format = match DeckFormat::load(format_name) {
+        Ok(format) => format,
+        Err(err) => {
+            eprintln!("failed to load format: {}", err);
+            return 2;
+        }
+    };
+    let deck: DeckList = match std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| ron::from_str(&text).map_err(|e| e.to_string()))
+    {
+        Ok(deck) => deck,
+        Err(err) => {
+            eprintln!("failed to load deck {}: {}", path, err);
+            return 2;
+        }
+    };
+    // 命令行中没有日志 卡片数据有错误时直接报告
+    let (card_database, errors) = CardDatabase::read(CARD_DATA_DIR);
+    if !errors.is_empty() {
+        for err in errors.iter() {
+            eprintln!("{}", err);
+        }
+        return 2;
+    }
+    let violations = format.validate(&deck, &card_database);
+    if violations.is_empty() {
+        println!("{} is legal in {}", deck.name, format.name);
+        return 0;
+    }
+    println!("{} is illegal in {}:", deck.name, format.name);
+    for violation in violations.iter() {
+        println!("  {}", violation);
+    }
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card_info::CardInfo;
+    use crate::card_info::card_enums::{Attr, Placement, Race};
+    use crate::deck::DeckEntry;
+
+    fn database() -> CardDatabase {
+        let mut database = CardDatabase::default();
+        for (id, card_type) in [
+            ("S001-A-001", CardType::Actor),
+            ("S001-M-001", CardType::Meme),
+            ("S002-A-001", CardType::Actor),
+        ] {
+            database.insert(CardInfo {
+                id: id.to_string(),
+                name: id.to_string(),
+                card_type,
+                attr: Attr::STAR,
+                race: Race::NULL,
+                cost: 0,
+                ack: 0,
+                placement: Placement::Normal,
+            });
+        }
+        database
+    }
+
+    fn format() -> DeckFormat {
+        DeckFormat {
+            name: "test".to_string(),
+            min_size: 2,
+            max_size: 8,
+            max_copies: 4,
+            type_quotas: vec![],
+            banned: vec![],
+            limited: vec![],
+            sets: vec![],
+        }
+    }
+
+    fn deck(cards: &[(&str, usize)]) -> DeckList {
+        DeckList {
+            name: "test".to_string(),
+            player: None,
+            cards: cards
+                .iter()
+                .map(|(id, count)| DeckEntry {
+                    id: id.to_string(),
+                    count: *count,
+                })
+                .collect(),
+            sideboard: vec![],
+        }
+    }
+
+    #[test]
+    fn legal_deck() {
+        let deck = deck(&[("S001-A-001", 4), ("S001-M-001", 2)]);
+        assert!(format().validate(&deck, &database()).is_empty());
+    }
+
+    #[test]
+    fn unknown_card() {
+        let deck = deck(&[("S001-A-001", 2), ("S009-A-001", 1)]);
+        assert_eq!(
+            format().validate(&deck, &database()),
+            vec![Violation::UnknownCard("S009-A-001".to_string())]
+        );
+    }
+
+    #[test]
+    fn deck_size() {
+        let database = database();
+        assert_eq!(
+            format().validate(&deck(&[("S001-A-001", 1)]), &database),
+            vec![Violation::TooFewCards { count: 1, min: 2 }]
+        );
+        let deck = deck(&[("S001-A-001", 4), ("S001-M-001", 4), ("S002-A-001", 1)]);
+        assert_eq!(
+            format().validate(&deck, &database),
+            vec![Violation::TooManyCards { count: 9, max: 8 }]
+        );
+    }
+
+    #[test]
+    fn too_many_copies() {
+        // 备牌也算在同名卡的数量里
+        let mut deck = deck(&[("S001-A-001", 3)]);
+        deck.sideboard.push(DeckEntry {
+            id: "S001-A-001".to_string(),
+            count: 2,
+        });
+        assert_eq!(
+            format().validate(&deck, &database()),
+            vec![Violation::TooManyCopies {
+                id: "S001-A-001".to_string(),
+                count: 5,
+                max: 4,
+            }]
+        );
+        // 限制卡使用自己的上限
+        let mut format = format();
+        format.limited.push(LimitedCard {
+            id: "S001-M-001".to_string(),
+            max: 1,
+        });
+        let deck = self::deck(&[("S001-A-001", 2), ("S001-M-001", 2)]);
+        assert_eq!(
+            format.validate(&deck, &database()),
+            vec![Violation::TooManyCopies {
+                id: "S001-M-001".to_string(),
+                count: 2,
+                max: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn banned() {
+        let mut format = format();
+        format.banned.push("S001-M-001".to_string());
+        let deck = deck(&[("S001-A-001", 2), ("S001-M-001", 1)]);
+        assert_eq!(
+            format.validate(&deck, &database()),
+            vec![Violation::Banned("S001-M-001".to_string())]
+        );
+    }
+
+    #[test]
+    fn type_quota() {
+        let mut format = format();
+        format.type_quotas.push(TypeQuota {
+            card_type: CardType::Actor,
+            min: 3,
+            max: 8,
+        });
+        let deck = deck(&[("S001-A-001", 2), ("S001-M-001", 2)]);
+        assert_eq!(
+            format.validate(&deck, &database()),
+            vec![Violation::TypeQuota {
+                card_type: CardType::Actor,
+                count: 2,
+                min: 3,
+                max: 8,
+            }]
+        );
+    }
+
+    #[test]
+    fn illegal_set() {
+        let mut format = format();
+        format.sets.push("S001".to_string());
+        let deck = deck(&[("S001-A-001", 2), ("S002-A-001", 1)]);
+        assert_eq!(
+            format.validate(&deck, &database()),
+            vec![Violation::IllegalSet {
+                id: "S002-A-001".to_string(),
+                set: "S002".to_string(),
+            }]
+        );
+    }
+}
@@ -1,9 +1,17 @@
+use crate::battle::BattlePlugin;
 use crate::card_info::CardInfoPlugin;
+use crate::card_zone::attach::AttachPlugin;
 use crate::card_zone::can_set::CardSetZonePlugin;
 use crate::debug_lab::DebugLabPlugin;
+use crate::deck::DeckPlugin;
 use crate::hand_card::HandCardPlugin;
 use crate::lua::LuaPlugin;
+use crate::match_state::MatchStatePlugin;
+use crate::replay::ReplayPlugin;
+use crate::snapshot::SnapshotPlugin;
+use crate::turn::TurnPlugin;
 use crate::ui::ShowDialogPlugin;
+use crate::ui::game_over::GameOverPlugin;
 use crate::zone_info::ZoneInfoPlugin;
 use bevy::prelude::*;
 
@@ -13,12 +21,20 @@ impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins((
             CardInfoPlugin,
+            DeckPlugin,
             ZoneInfoPlugin,
             HandCardPlugin,
             LuaPlugin,
+            MatchStatePlugin,
+            TurnPlugin,
+            BattlePlugin,
             CardSetZonePlugin,
+            AttachPlugin,
             DebugLabPlugin,
             ShowDialogPlugin,
+            GameOverPlugin,
+            ReplayPlugin,
+            SnapshotPlugin,
         ));
     }
 }
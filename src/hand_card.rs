@@ -1,3 +1,4 @@
+use crate::rules::PlayerId;
 use bevy::prelude::*;
 use bevy_card3d_kit::prelude::card_state::CardState;
 use bevy_card3d_kit::prelude::{CardLine, HAND_CARD_LEVEL};
@@ -20,6 +21,7 @@ fn setup(mut commands: Commands) {
                 face_up: true,
                 vertical: true,
             },
+            PlayerId::MY,
         ))
         .id();
     let opponent_card_line_entity = commands
@@ -32,6 +34,7 @@ fn setup(mut commands: Commands) {
                 face_up: false,
                 vertical: true,
             },
+            PlayerId::OPPONENT,
         ))
         .id();
 
@@ -46,3 +49,24 @@ pub struct CardLineResource {
     pub my_card_line: Entity,
     pub opponent_card_line: Entity,
 }
+
+impl CardLineResource {
+    pub fn card_line(&self, player: PlayerId) -> Entity {
+        if player == PlayerId::MY {
+            self.my_card_line
+        } else {
+            self.opponent_card_line
+        }
+    }
+
+    // 手卡对应的玩家
+    pub fn player_of(&self, card_line: Entity) -> Option<PlayerId> {
+        if card_line == self.my_card_line {
+            Some(PlayerId::MY)
+        } else if card_line == self.opponent_card_line {
+            Some(PlayerId::OPPONENT)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use crate::lua::query::{GameView, card_table, zone_key};
+use crate::lua::register_api;
+use crate::rules::{Action, CardId, Effect, EffectDuration, Location, PlayerId, ZoneKey};
+use bevy::prelude::*;
+use bevy_scriptum::runtimes::lua::prelude::*;
+use mlua::{Function, Table, Value};
+use std::sync::{Arc, Mutex};
+
+/// 脚本发起的效果
+/// 脚本不直接修改实体 效果作为操作交给规则核心检查 通过后再同步到场景
+/// draw(player, n) move_card(card, zone_type, slot) destroy(card)
+/// modify_ack(card, delta, duration) discard(player, n) search_deck(player, filter)
+
+// 当前触发的卡片和等待提交的操作
+#[derive(Default, Debug)]
+struct CommandQueue {
+    source: Option<CardId>,
+    actions: Vec<Action>,
+}
+
+#[derive(Resource, Clone, Default)]
+pub struct LuaCommands(Arc<Mutex<CommandQueue>>);
+
+impl LuaCommands {
+    // 调用卡片的方法前设置效果的来源
+    pub fn begin(&self, source: CardId) {
+        self.0.lock().unwrap().source = Some(source);
+    }
+
+    pub fn end(&self) {
+        self.0.lock().unwrap().source = None;
+    }
+
+    pub fn drain(&self) -> Vec<Action> {
+        std::mem::take(&mut self.0.lock().unwrap().actions)
+    }
+
+    // 不在效果触发中调用时报错
+    fn push(&self, effect: Effect) -> mlua::Result<()> {
+        let mut queue = self.0.lock().unwrap();
+        let Some(source) = queue.source else {
+            return Err(mlua::Error::runtime(
+                "card effects can only be used inside a card trigger",
+            ));
+        };
+        queue.actions.push(Action::Effect { source, effect });
+        Ok(())
+    }
+}
+
+// nil 或 "turn" 到回合结束 "permanent" 永久 数字为持续的回合数
+// 回合数必须是 1..=u32::MAX 的整数
+fn effect_duration(duration: Value) -> mlua::Result<EffectDuration> {
+    let out_of_range = |turns: &dyn std::fmt::Display| {
+        mlua::Error::runtime(format!("duration {} is out of range", turns))
+    };
+    match duration {
+        Value::Nil => Ok(EffectDuration::EndOfTurn),
+        Value::Integer(turns) => u32::try_from(turns)
+            .ok()
+            .filter(|turns| *turns > 0)
+            .map(EffectDuration::Turns)
+            .ok_or_else(|| out_of_range(&turns)),
+        Value::Number(turns)
+            if turns.fract() == 0.0 && (1.0..=u32::MAX as f64).contains(&turns) =>
+        {
+            Ok(EffectDuration::Turns(turns as u32))
+        }
+        Value::Number(turns) => Err(out_of_range(&turns)),
+        Value::String(name) => match name.to_str()? {
+            "turn" => Ok(EffectDuration::EndOfTurn),
+            "permanent" => Ok(EffectDuration::Permanent),
+            other => Err(mlua::Error::runtime(format!("unknown duration {}", other))),
+        },
+        other => Err(mlua::Error::runtime(format!(
+            "unknown duration {:?}",
+            other
+        ))),
+    }
+}
+
+pub(super) fn register_command_api(
+    lua_commands: Res<LuaCommands>,
+    game_view: Res<GameView>,
+    mut scripting_runtime: ResMut<LuaRuntime>,
+) {
+    let commands = lua_commands.clone();
+    let view = game_view.clone();
+    register_api(&mut scripting_runtime, "commands", |engine| {
+        let draw_commands = commands.clone();
+        let draw = engine.create_function(move |_, (player, count): (u8, usize)| {
+            draw_commands.push(Effect::Draw {
+                player: PlayerId(player),
+                count,
+            })
+        })?;
+        engine.globals().set("draw", draw)?;
+
+        // 移动到卡片持有者的区域 "Hand" 为手卡
+        let move_commands = commands.clone();
+        let move_view = view.clone();
+        let move_card = engine.create_function(
+            move |_, (card, zone_type, slot): (CardId, String, Option<usize>)| {
+                let owner = {
+                    let state = move_view.0.lock().unwrap();
+                    state
+                        .card(card)
+                        .map(|c| c.owner)
+                        .map_err(mlua::Error::runtime)?
+                };
+                let to = if zone_type == "Hand" {
+                    Location::Hand(owner)
+                } else {
+                    let Some(key) = zone_key(&zone_type, slot) else {
+                        return Err(mlua::Error::runtime(format!(
+                            "unknown zone {} {:?}",
+                            zone_type, slot
+                        )));
+                    };
+                    Location::Zone(owner, key)
+                };
+                move_commands.push(Effect::MoveCard { card, to })
+            },
+        )?;
+        engine.globals().set("move_card", move_card)?;
+
+        let destroy_commands = commands.clone();
+        let destroy = engine.create_function(move |_, card: CardId| {
+            destroy_commands.push(Effect::Destroy { card })
+        })?;
+        engine.globals().set("destroy", destroy)?;
+
+        let ack_commands = commands.clone();
+        let modify_ack =
+            engine.create_function(move |_, (card, delta, duration): (CardId, i32, Value)| {
+                ack_commands.push(Effect::ModifyAck {
+                    card,
+                    delta,
+                    duration: effect_duration(duration)?,
+                })
+            })?;
+        engine.globals().set("modify_ack", modify_ack)?;
+
+        let discard_commands = commands.clone();
+        let discard = engine.create_function(move |_, (player, count): (u8, usize)| {
+            discard_commands.push(Effect::Discard {
+                player: PlayerId(player),
+                count,
+            })
+        })?;
+        engine.globals().set("discard", discard)?;
+
+        // 从卡组顶开始找第一张符合条件的卡 返回卡片编号 没有时返回 nil
+        let search_commands = commands.clone();
+        let search_deck =
+            engine.create_function(move |lua, (player, filter): (u8, Function)| {
+                // 调用过滤方法前释放锁 过滤方法中可以继续查询
+                let cards: Vec<Table> = {
+                    let state = view.0.lock().unwrap();
+                    let Ok(player_state) = state.player(PlayerId(player)) else {
+                        return Ok(None);
+                    };
+                    let mut cards = vec![];
+                    for card in player_state.zone(ZoneKey::Desk).iter().rev() {
+                        if let Some(table) = card_table(lua, &state, *card)? {
+                            cards.push(table);
+                        }
+                    }
+                    cards
+                };
+                for table in cards {
+                    if filter.call::<_, bool>(table.clone())? {
+                        let card: CardId = table.get("id")?;
+                        search_commands.push(Effect::SearchDeck {
+                            player: PlayerId(player),
+                            card,
+                        })?;
+                        return Ok(Some(card));
+                    }
+                }
+                Ok(None)
+            })?;
+        engine.globals().set("search_deck", search_deck)?;
+        Ok(())
+    });
+}
@@ -0,0 +1,179 @@
+use crate::card_info::database::script_path;
+use crate::match_state::{CardEntityMap, CardInstanceId};
+use bevy::color::palettes::css;
+use bevy::prelude::*;
+use bevy_scriptum::Script;
+use bevy_scriptum::runtimes::lua::LuaScript;
+use bevy_scriptum::runtimes::lua::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+/// 脚本诊断
+/// 卡片脚本出错时记录脚本路径 卡片和错误信息 不会让游戏崩溃
+/// 出错的卡片标记为损坏 显示红色的标记 之后不再调用它的脚本
+
+// 损坏卡片上的标记 盖在卡面中间
+const PLACEHOLDER_COLOR: Srgba = css::RED;
+const PLACEHOLDER_SIZE: Vec2 = Vec2::new(0.8, 0.3);
+const PLACEHOLDER_OFFSET: Vec3 = Vec3::new(0.0, 0.0, 0.01);
+
+#[derive(Event, Clone, Debug)]
+pub struct ScriptDiagnostic {
+    pub card: CardInstanceId,
+    // 相对 assets
+    pub script: String,
+    pub message: String,
+}
+
+impl ScriptDiagnostic {
+    pub fn new(card: CardInstanceId, id: &str, message: impl Display) -> Self {
+        Self {
+            card,
+            script: script_path(id),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl Display for ScriptDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({}): {}", self.script, self.card, self.message)
+    }
+}
+
+// 本次运行中所有的诊断 按发生的顺序
+#[derive(Resource, Clone, Debug, Default)]
+pub struct ScriptDiagnostics(pub Vec<ScriptDiagnostic>);
+
+// 脚本出错的卡片 保留第一个错误
+#[derive(Component, Clone, Debug)]
+pub struct BrokenCard {
+    pub message: String,
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct BrokenCardPlaceholder;
+
+// 脚本加载失败或有语法错误时 使用这个脚本的卡片都会损坏
+// 记住出错的脚本 之后生成的卡片使用同一个脚本时也会损坏
+pub(super) fn check_scripts(
+    mut asset_events: EventReader<AssetEvent<LuaScript>>,
+    mut failed_events: EventReader<AssetLoadFailedEvent<LuaScript>>,
+    mut broken_scripts: Local<HashMap<AssetId<LuaScript>, (String, String)>>,
+    scripts: Res<Assets<LuaScript>>,
+    asset_server: Res<AssetServer>,
+    query: Query<(&CardInstanceId, Ref<Script<LuaScript>>)>,
+    mut diagnostics: EventWriter<ScriptDiagnostic>,
+    mut scripting_runtime: ResMut<LuaRuntime>,
+) {
+    let mut changed = HashSet::new();
+    for failed in failed_events.read() {
+        broken_scripts.insert(
+            failed.id,
+            (failed.path.to_string(), failed.error.to_string()),
+        );
+        changed.insert(failed.id);
+    }
+    for event in asset_events.read() {
+        // 修改后重新加载的脚本也要检查
+        let (AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id }) = event
+        else {
+            continue;
+        };
+        let Some(script) = scripts.get(*id) else {
+            continue;
+        };
+        let path = asset_server
+            .get_path(*id)
+            .map(|path| path.to_string())
+            .unwrap_or_default();
+        // 只编译不执行
+        let result = scripting_runtime.with_engine_mut(|engine| {
+            engine
+                .load(script.0.as_str())
+                .set_name(path.as_str())
+                .into_function()
+                .map(|_| ())
+        });
+        match result {
+            Ok(()) => {
+                broken_scripts.remove(id);
+            }
+            Err(err) => {
+                broken_scripts.insert(*id, (path, err.to_string()));
+                changed.insert(*id);
+            }
+        }
+    }
+    if broken_scripts.is_empty() {
+        return;
+    }
+    for (card, script) in query.iter() {
+        let id = script.script.id();
+        if !script.is_added() && !changed.contains(&id) {
+            continue;
+        }
+        if let Some((path, message)) = broken_scripts.get(&id) {
+            diagnostics.write(ScriptDiagnostic {
+                card: *card,
+                script: path.clone(),
+                message: message.clone(),
+            });
+        }
+    }
+}
+
+pub(super) fn mark_broken_cards(
+    mut commands: Commands,
+    mut diagnostic_events: EventReader<ScriptDiagnostic>,
+    mut diagnostics: ResMut<ScriptDiagnostics>,
+    card_entity_map: Res<CardEntityMap>,
+    query: Query<(), With<BrokenCard>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    // 同一帧内的多个错误只标记一次
+    let mut marked = HashSet::new();
+    for diagnostic in diagnostic_events.read() {
+        warn!("script error {}", diagnostic);
+        diagnostics.0.push(diagnostic.clone());
+        let Some(entity) = card_entity_map.entity(diagnostic.card.0) else {
+            continue;
+        };
+        if query.contains(entity) || !marked.insert(entity) {
+            continue;
+        }
+        commands
+            .entity(entity)
+            .insert(BrokenCard {
+                message: diagnostic.message.clone(),
+            })
+            .with_children(|parent| {
+                parent.spawn((
+                    BrokenCardPlaceholder,
+                    Mesh3d(meshes.add(Rectangle::from_size(PLACEHOLDER_SIZE))),
+                    MeshMaterial3d(materials.add(StandardMaterial {
+                        base_color: PLACEHOLDER_COLOR.with_alpha(0.8).into(),
+                        alpha_mode: AlphaMode::Blend,
+                        unlit: true,
+                        ..Default::default()
+                    })),
+                    Transform::from_translation(PLACEHOLDER_OFFSET),
+                    Pickable::IGNORE,
+                ));
+            });
+    }
+}
+
+// 脚本重新加载成功后卡片不再损坏 去掉标记
+pub(super) fn clear_placeholders(
+    mut commands: Commands,
+    query: Query<(Entity, &ChildOf), With<BrokenCardPlaceholder>>,
+    query_broken: Query<(), With<BrokenCard>>,
+) {
+    for (entity, child_of) in query.iter() {
+        if !query_broken.contains(child_of.parent()) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
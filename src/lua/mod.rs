@@ -1,15 +1,70 @@
+use crate::asset_dir::asset_path;
 use crate::card_info::CardInfo;
-use crate::card_info::card_enums::{Attr, CardType, Race};
+use crate::card_info::card_enums::Placement;
+use crate::lua::diagnostic::{BrokenCard, ScriptDiagnostic};
+use crate::match_state::CardInstanceId;
+use crate::rules::rng::MatchRng;
+use crate::rules::{CardId, PlayerId};
+use crate::zone_info::{ZoneRegistry, ZoneType};
 use bevy::prelude::*;
 use bevy_card3d_kit::prelude::Card;
 use bevy_scriptum::prelude::*;
 use bevy_scriptum::runtimes::lua::prelude::*;
 use mlua::prelude::LuaUserDataFields;
-use mlua::{UserData, Value};
+use mlua::{AnyUserData, FromLuaMulti, IntoLua, Lua, Table, UserData, Value};
+use std::collections::HashMap;
 use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 
-// 卡片信息的方法名称
+pub mod command;
+pub mod diagnostic;
+pub mod query;
+pub mod reload;
+pub mod trigger;
+
+// 卡片信息的方法名称 卡片信息一般来自卡片数据库 脚本中定义时会覆盖数据库
 pub const CARD_INFO_FUNC: &str = "get_card_info";
+// 神秘术发动效果的方法名称
+pub const ARCANE_CAST_FUNC: &str = "on_cast";
+// 脚本中 require 的模块所在的目录 相对 assets 修改后需要重启
+pub const LUA_LIB_DIR: &str = "lua/lib";
+// 卡片模板的模块名称 加载为全局的 Card
+pub const CARD_TEMPLATE: &str = "card";
+
+// 脚本使用的随机数
+// 调用脚本前从对局借出 调用结束后还回去 保证整局只有一个随机数序列
+// 没有借出时 例如加载脚本的时候 脚本不能使用随机数
+#[derive(Resource, Clone, Default)]
+pub struct LuaRng(Arc<Mutex<Option<MatchRng>>>);
+
+impl LuaRng {
+    pub fn lend(&self, rng: MatchRng) {
+        *self.0.lock().unwrap() = Some(rng);
+    }
+
+    pub fn take(&self) -> Option<MatchRng> {
+        self.0.lock().unwrap().take()
+    }
+
+    fn with<T>(&self, f: impl FnOnce(&mut MatchRng) -> T) -> mlua::Result<T> {
+        match self.0.lock().unwrap().as_mut() {
+            Some(rng) => Ok(f(rng)),
+            None => Err(mlua::Error::runtime(
+                "random can only be used in card effects",
+            )),
+        }
+    }
+}
+
+// 实体编号到卡片编号 脚本加载时用来确定命名空间
+// 脚本加载时实体一定存活 所以只用编号不会冲突
+#[derive(Resource, Clone, Default)]
+pub struct LuaCardIds(Arc<Mutex<HashMap<u32, CardId>>>);
+
+// 卡片在脚本中的命名空间 和实体无关 存档读取后也不会变
+pub fn card_ns(card: CardInstanceId) -> String {
+    format!("card_{}", card.0)
+}
 
 pub struct LuaPlugin;
 
@@ -18,66 +73,267 @@ impl Plugin for LuaPlugin {
         app.add_scripting::<LuaRuntime>(|runtime| {
             // todo
         });
-        app.add_systems(Startup, setup);
-        app.add_systems(Update, add_card_info_by_script);
+        app.init_resource::<LuaRng>();
+        app.init_resource::<LuaCardIds>();
+        app.init_resource::<query::GameView>();
+        app.init_resource::<command::LuaCommands>();
+        app.init_resource::<diagnostic::ScriptDiagnostics>();
+        app.add_event::<diagnostic::ScriptDiagnostic>();
+        app.add_systems(
+            Startup,
+            (
+                setup,
+                query::register_game_api,
+                command::register_command_api,
+            ),
+        );
+        app.add_systems(PostStartup, register_zone_api);
+        app.add_systems(PreUpdate, sync_lua_card_ids);
+        app.add_systems(
+            Update,
+            (
+                diagnostic::check_scripts,
+                reload::reload_scripts,
+                add_card_info_by_script,
+                query::sync_game_view,
+                (
+                    diagnostic::clear_placeholders,
+                    diagnostic::mark_broken_cards,
+                ),
+            )
+                .chain(),
+        );
     }
 }
 
 impl UserData for CardInfo {}
-fn setup(mut scripting_runtime: ResMut<LuaRuntime>) {
-    scripting_runtime.with_engine_mut(|engine| {
-        engine
-            .register_userdata_type::<CardInfo>(|test| {
-                test.add_field_method_get("id", |_, this| Ok(this.clone().id));
-                test.add_field_method_get("name", |_, this| Ok(this.clone().name));
-            })
-            .unwrap();
-        let test_constructor = engine
-            .create_function(
-                |_,
-                 (id, name, card_type, attr, race, cost, ack): (
-                    String,
-                    String,
-                    String,
-                    String,
-                    String,
-                    usize,
-                    u32,
-                )| {
-                    Ok(CardInfo {
-                        id,
-                        name,
-                        card_type: CardType::from_str(card_type.as_str()).unwrap(),
-                        attr: Attr::from_str(attr.as_str()).unwrap(),
-                        race: Race::from_str(race.as_str()).unwrap(),
-                        cost,
-                        ack,
+
+// 注册脚本接口 失败时只记录错误 游戏继续运行
+fn register_api(
+    scripting_runtime: &mut LuaRuntime,
+    name: &str,
+    register: impl FnOnce(&mut Lua) -> mlua::Result<()>,
+) {
+    if let Err(err) = scripting_runtime.with_engine_mut(register) {
+        error!("register lua {} failed: {}", name, err);
+    }
+}
+
+// 卡片信息中的枚举 写错时返回脚本错误
+fn parse_enum<T: FromStr>(field: &str, value: &str) -> mlua::Result<T> {
+    T::from_str(value).map_err(|_| mlua::Error::runtime(format!("unknown {} \"{}\"", field, value)))
+}
+
+fn setup(
+    lua_rng: Res<LuaRng>,
+    lua_card_ids: Res<LuaCardIds>,
+    mut scripting_runtime: ResMut<LuaRuntime>,
+) {
+    let random_rng = lua_rng.clone();
+    let range_rng = lua_rng.clone();
+    let card_ids = lua_card_ids.clone();
+    let id_card_ids = lua_card_ids.clone();
+    register_api(&mut scripting_runtime, "globals", |engine| {
+        // card_ns(entity.index) 返回卡片的命名空间
+        let card_ns_fn = engine.create_function(move |_, index: u32| {
+            Ok(card_ids
+                .0
+                .lock()
+                .unwrap()
+                .get(&index)
+                .map(|card| card_ns(CardInstanceId(*card))))
+        })?;
+        engine.globals().set("card_ns", card_ns_fn)?;
+        // card_id(entity.index) 返回对局内的卡片编号
+        let card_id_fn = engine.create_function(move |_, index: u32| {
+            Ok(id_card_ids.0.lock().unwrap().get(&index).copied())
+        })?;
+        engine.globals().set("card_id", card_id_fn)?;
+        // random(max) 返回 1..=max
+        let random = engine.create_function(move |_, max: usize| {
+            if max == 0 {
+                return Err(mlua::Error::runtime("random(max) needs max >= 1"));
+            }
+            random_rng.with(|rng| rng.below(max) + 1)
+        })?;
+        engine.globals().set("random", random)?;
+        // random_range(min, max) 返回 min..=max
+        let random_range = engine.create_function(move |_, (min, max): (i64, i64)| {
+            if max < min {
+                return Ok(min);
+            }
+            let n = (max - min) as usize + 1;
+            range_rng.with(|rng| min + rng.below(n) as i64)
+        })?;
+        engine.globals().set("random_range", random_range)?;
+        engine.register_userdata_type::<CardInfo>(|test| {
+            test.add_field_method_get("id", |_, this| Ok(this.clone().id));
+            test.add_field_method_get("name", |_, this| Ok(this.clone().name));
+            test.add_field_method_get("card_type", |_, this| Ok(format!("{:?}", this.card_type)));
+            test.add_field_method_get("attr", |_, this| Ok(format!("{:?}", this.attr)));
+            test.add_field_method_get("race", |_, this| Ok(format!("{:?}", this.race)));
+            test.add_field_method_get("cost", |_, this| Ok(this.cost));
+            test.add_field_method_get("ack", |_, this| Ok(this.ack));
+            test.add_field_method_get("placement", |_, this| Ok(format!("{:?}", this.placement)));
+        })?;
+        let test_constructor = engine.create_function(
+            |_,
+             (id, name, card_type, attr, race, cost, ack, placement): (
+                String,
+                String,
+                String,
+                String,
+                String,
+                usize,
+                u32,
+                Option<String>,
+            )| {
+                Ok(CardInfo {
+                    id,
+                    name,
+                    card_type: parse_enum("card_type", &card_type)?,
+                    attr: parse_enum("attr", &attr)?,
+                    race: parse_enum("race", &race)?,
+                    cost,
+                    ack,
+                    placement: match placement {
+                        Some(placement) => parse_enum("placement", &placement)?,
+                        None => Placement::default(),
+                    },
+                })
+            },
+        )?;
+        engine.globals().set("CardInfo", test_constructor)?;
+        Ok(())
+    });
+    register_api(&mut scripting_runtime, "modules", |engine| {
+        let package: Table = engine.globals().get("package")?;
+        let lib_dir = asset_path(LUA_LIB_DIR);
+        package.set(
+            "path",
+            format!("{0}/?.lua;{0}/?/init.lua", lib_dir.display()),
+        )?;
+        let code = format!("return require(\"{}\")", CARD_TEMPLATE);
+        let template: Value = engine
+            .load(code.as_str())
+            .set_name("card template")
+            .eval()?;
+        engine.globals().set("Card", template)?;
+        Ok(())
+    });
+}
+
+// 场地查询 玩家用数字表示 场地用实体编号表示
+// Zones.get(player, zone_type, slot) Zones.iter(player, zone_type) Zones.owner_and_type(zone)
+fn register_zone_api(zone_registry: Res<ZoneRegistry>, mut scripting_runtime: ResMut<LuaRuntime>) {
+    let registry = zone_registry.clone();
+    register_api(&mut scripting_runtime, "Zones", |engine| {
+        let zones = engine.create_table()?;
+        let get_registry = registry.clone();
+        let get = engine.create_function(
+            move |_, (player, zone_type, slot): (u8, String, Option<usize>)| {
+                Ok(ZoneType::from_str(zone_type.as_str())
+                    .ok()
+                    .and_then(|zone_type| {
+                        get_registry.get(PlayerId(player), zone_type, slot.unwrap_or(1))
                     })
-                },
-            )
-            .unwrap();
-        engine.globals().set("CardInfo", test_constructor).unwrap();
+                    .map(|entity| entity.to_bits()))
+            },
+        )?;
+        zones.set("get", get)?;
+        let iter_registry = registry.clone();
+        let iter = engine.create_function(move |_, (player, zone_type): (u8, String)| {
+            let mut result = HashMap::new();
+            if let Ok(zone_type) = ZoneType::from_str(zone_type.as_str()) {
+                for (key, entity) in iter_registry.iter(PlayerId(player), zone_type) {
+                    result.insert(key.slot().unwrap_or(1), entity.to_bits());
+                }
+            }
+            Ok(result)
+        })?;
+        zones.set("iter", iter)?;
+        let owner_and_type = engine.create_function(move |_, zone: u64| {
+            Ok(Entity::try_from_bits(zone)
+                .ok()
+                .and_then(|entity| registry.owner_and_type(entity))
+                .map(|(player, zone_type)| (player.0, format!("{:?}", zone_type)))
+                .unzip())
+        })?;
+        zones.set("owner_and_type", owner_and_type)?;
+        engine.globals().set("Zones", zones)?;
+        Ok(())
     });
 }
 
+fn sync_lua_card_ids(
+    lua_card_ids: Res<LuaCardIds>,
+    query: Query<(Entity, &CardInstanceId), Changed<CardInstanceId>>,
+    mut removed: RemovedComponents<CardInstanceId>,
+) {
+    let mut card_ids = lua_card_ids.0.lock().unwrap();
+    for entity in removed.read() {
+        card_ids.remove(&entity.index());
+    }
+    for (entity, card) in query.iter() {
+        card_ids.insert(entity.index(), card.0);
+    }
+}
+
+// 调用卡片脚本中的方法 第一个参数是卡片自己的表
+// 卡片没有脚本或者脚本中没有这个方法时返回 None
+pub(crate) fn call_card_fn<'lua, A: IntoLua<'lua>, R: FromLuaMulti<'lua>>(
+    engine: &'lua Lua,
+    card: CardInstanceId,
+    name: &str,
+    args: A,
+) -> mlua::Result<Option<R>> {
+    let Value::Table(table) = engine.globals().get::<_, Value>(card_ns(card))? else {
+        return Ok(None);
+    };
+    let Value::Function(func) = table.get::<_, Value>(name)? else {
+        return Ok(None);
+    };
+    func.call((table, args)).map(Some)
+}
+
+// 脚本中定义的卡片信息 没有 get_card_info 时返回 None
+fn script_card_info(engine: &Lua, card: CardInstanceId) -> mlua::Result<Option<CardInfo>> {
+    let Value::Table(_) = engine.globals().get::<_, Value>(card_ns(card))? else {
+        return Err(mlua::Error::runtime(
+            "script does not define its card table, use Card.define { .. }",
+        ));
+    };
+    let Some(data) =
+        call_card_fn::<_, Option<AnyUserData>>(engine, card, CARD_INFO_FUNC, Value::Nil)?.flatten()
+    else {
+        return Ok(None);
+    };
+    let info = data.borrow::<CardInfo>().map_err(|_| {
+        mlua::Error::runtime(format!("{} must return CardInfo(..)", CARD_INFO_FUNC))
+    })?;
+    Ok(Some(info.clone()))
+}
+
+// 脚本没有定义命名空间或者卡片信息有误时 卡片损坏 保留数据库中的信息
 fn add_card_info_by_script(
     mut commands: Commands,
-    mut scripted_entities: Query<(Entity, &mut LuaScriptData), (With<Card>, Added<LuaScriptData>)>,
+    scripted_entities: Query<
+        (Entity, &CardInstanceId, &CardInfo),
+        (With<Card>, Added<LuaScriptData>, Without<BrokenCard>),
+    >,
+    mut diagnostics: EventWriter<ScriptDiagnostic>,
     mut scripting_runtime: ResMut<LuaRuntime>,
 ) {
-    for (entity, mut script_data) in &mut scripted_entities {
-        if let Ok(lua_value) =
-            scripting_runtime.call_fn_with_ns(CARD_INFO_FUNC, &mut script_data, entity, ())
-        {
-            scripting_runtime.with_engine_mut(|engine| {
-                if let Ok(value) = engine.registry_value::<Value>(&lua_value.0) {
-                    if let Value::UserData(data) = value {
-                        if let Ok(card_info) = data.borrow::<CardInfo>() {
-                            commands.entity(entity).insert(card_info.clone());
-                        }
-                    }
-                }
-            });
+    for (entity, card, database_info) in scripted_entities.iter() {
+        let result = scripting_runtime.with_engine_mut(|engine| script_card_info(engine, *card));
+        match result {
+            Ok(Some(card_info)) => {
+                commands.entity(entity).insert(card_info);
+            }
+            Ok(None) => {}
+            Err(err) => {
+                diagnostics.write(ScriptDiagnostic::new(*card, &database_info.id, err));
+            }
         }
     }
 }
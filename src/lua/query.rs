@@ -0,0 +1,167 @@
+use crate::lua::register_api;
+use crate::match_state::MatchState;
+use crate::rules::state::GameState;
+use crate::rules::{CardId, Location, PlayerId, ZoneKey};
+use crate::zone_info::ZoneType;
+use bevy::prelude::*;
+use bevy_scriptum::runtimes::lua::prelude::*;
+use mlua::{Lua, Table};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+/// 脚本查询对局
+/// 玩家用数字表示 区域用 Zones 中的类型名称和编号表示 卡片用对局内的编号表示
+/// Game.card(card) Game.zone_cards(player, zone_type, slot) Game.zone_count(player, zone_type, slot)
+/// Game.hand(player) Game.counts(player) Game.turn() Game.phase() Game.active_player()
+
+// 规则核心的副本 对局变化后更新 脚本只能读取
+#[derive(Resource, Clone, Default)]
+pub struct GameView(pub(super) Arc<Mutex<GameState>>);
+
+// 卡片位置的名称 和 ZoneType 一致 另外有 Hand 和 Attached
+pub(super) fn location_name(location: Location) -> String {
+    match location {
+        Location::Hand(_) => "Hand".to_string(),
+        Location::Zone(_, key) => format!("{:?}", ZoneType::from(key)),
+        Location::Attached(..) => "Attached".to_string(),
+    }
+}
+
+// 没有编号的区域忽略 slot
+pub(super) fn zone_key(zone_type: &str, slot: Option<usize>) -> Option<ZoneKey> {
+    ZoneType::from_str(zone_type)
+        .ok()?
+        .key(Some(slot.unwrap_or(1)))
+}
+
+fn zone_cards(state: &GameState, player: u8, zone_type: &str, slot: Option<usize>) -> Vec<CardId> {
+    match (state.player(PlayerId(player)), zone_key(zone_type, slot)) {
+        (Ok(player_state), Some(key)) => player_state.zone(key).to_vec(),
+        _ => vec![],
+    }
+}
+
+pub(super) fn card_table<'lua>(
+    lua: &'lua Lua,
+    state: &GameState,
+    card: CardId,
+) -> mlua::Result<Option<Table<'lua>>> {
+    let Ok(instance) = state.card(card) else {
+        return Ok(None);
+    };
+    let table = lua.create_table()?;
+    table.set("id", card)?;
+    table.set("owner", instance.owner.0)?;
+    table.set("controller", instance.location.player().0)?;
+    table.set("zone", location_name(instance.location))?;
+    match instance.location {
+        Location::Zone(_, key) => table.set("slot", key.slot())?,
+        Location::Attached(_, host) => table.set("host", host)?,
+        Location::Hand(_) => {}
+    }
+    table.set("face_up", instance.pose.face_up)?;
+    table.set("vertical", instance.pose.vertical)?;
+    table.set("info", instance.info.clone())?;
+    // 包括装备和效果的修正
+    table.set("ack", state.effective_ack(card))?;
+    if let Ok(player_state) = state.player(instance.owner) {
+        table.set("equipped", player_state.attachments(card).to_vec())?;
+    }
+    Ok(Some(table))
+}
+
+pub(super) fn sync_game_view(match_state: Res<MatchState>, game_view: Res<GameView>) {
+    if match_state.is_changed() {
+        *game_view.0.lock().unwrap() = match_state.0.clone();
+    }
+}
+
+pub(super) fn register_game_api(
+    game_view: Res<GameView>,
+    mut scripting_runtime: ResMut<LuaRuntime>,
+) {
+    let view = game_view.clone();
+    register_api(&mut scripting_runtime, "Game", |engine| {
+        let game = engine.create_table()?;
+
+        let card_view = view.clone();
+        let card = engine.create_function(move |lua, card: CardId| {
+            card_table(lua, &card_view.0.lock().unwrap(), card)
+        })?;
+        game.set("card", card)?;
+
+        let zone_view = view.clone();
+        let zone_cards_fn = engine.create_function(
+            move |_, (player, zone_type, slot): (u8, String, Option<usize>)| {
+                Ok(zone_cards(
+                    &zone_view.0.lock().unwrap(),
+                    player,
+                    &zone_type,
+                    slot,
+                ))
+            },
+        )?;
+        game.set("zone_cards", zone_cards_fn)?;
+
+        let count_view = view.clone();
+        let zone_count = engine.create_function(
+            move |_, (player, zone_type, slot): (u8, String, Option<usize>)| {
+                Ok(zone_cards(&count_view.0.lock().unwrap(), player, &zone_type, slot).len())
+            },
+        )?;
+        game.set("zone_count", zone_count)?;
+
+        let hand_view = view.clone();
+        let hand = engine.create_function(move |_, player: u8| {
+            Ok(hand_view
+                .0
+                .lock()
+                .unwrap()
+                .player(PlayerId(player))
+                .map(|p| p.hand.clone())
+                .unwrap_or_default())
+        })?;
+        game.set("hand", hand)?;
+
+        // 手卡 卡组 墓地 理性区 激情区的数量和生命
+        let counts_view = view.clone();
+        let counts = engine.create_function(move |lua, player: u8| {
+            let state = counts_view.0.lock().unwrap();
+            let Ok(player_state) = state.player(PlayerId(player)) else {
+                return Ok(None);
+            };
+            let table = lua.create_table()?;
+            table.set("hand", player_state.hand.len())?;
+            table.set("desk", player_state.zone(ZoneKey::Desk).len())?;
+            table.set("grave", player_state.zone(ZoneKey::Grave).len())?;
+            table.set("lx", player_state.zone(ZoneKey::Lx).len())?;
+            table.set("jq", player_state.zone(ZoneKey::Jq).len())?;
+            table.set("life", player_state.life)?;
+            Ok(Some(table))
+        })?;
+        game.set("counts", counts)?;
+
+        let turn_view = view.clone();
+        let turn = engine.create_function(move |_, ()| Ok(turn_view.0.lock().unwrap().turn))?;
+        game.set("turn", turn)?;
+
+        // 对局开始前为 nil
+        let phase_view = view.clone();
+        let phase = engine.create_function(move |_, ()| {
+            Ok(phase_view
+                .0
+                .lock()
+                .unwrap()
+                .phase
+                .map(|phase| format!("{:?}", phase)))
+        })?;
+        game.set("phase", phase)?;
+
+        let active_player =
+            engine.create_function(move |_, ()| Ok(view.0.lock().unwrap().active_player.0))?;
+        game.set("active_player", active_player)?;
+
+        engine.globals().set("Game", game)?;
+        Ok(())
+    });
+}
@@ -0,0 +1,104 @@
+use crate::card_info::CardInfo;
+use crate::card_info::database::CardDatabase;
+use crate::lua::diagnostic::{BrokenCard, ScriptDiagnostic};
+use crate::lua::{card_ns, script_card_info};
+use crate::match_state::CardInstanceId;
+use bevy::prelude::*;
+use bevy_scriptum::Script;
+use bevy_scriptum::runtimes::lua::LuaScript;
+use bevy_scriptum::runtimes::lua::prelude::*;
+use mlua::{Function, Lua, Value};
+
+/// 脚本热重载
+/// 运行中修改卡片脚本后 对每一张使用这个脚本的卡片重新执行脚本 刷新卡片信息和效果
+/// 需要 hot_reload 特性 默认开启
+
+// 重新执行卡片的脚本 旧表中的状态在新表没有定义时保留 例如装备的人物
+fn reload_card(
+    engine: &Lua,
+    chunk: &Function,
+    entity: Entity,
+    card: CardInstanceId,
+) -> mlua::Result<()> {
+    let globals = engine.globals();
+    let ns = card_ns(card);
+    let old: Value = globals.get(ns.as_str())?;
+    // 脚本通过 entity.index 找到自己的命名空间
+    let old_entity: Value = globals.get("entity")?;
+    let entity_table = engine.create_table()?;
+    entity_table.set("index", entity.index())?;
+    globals.set("entity", entity_table)?;
+    let result = chunk.call::<_, ()>(());
+    globals.set("entity", old_entity)?;
+    result?;
+    if let (Value::Table(old), Value::Table(new)) = (old, globals.get::<_, Value>(ns.as_str())?) {
+        for pair in old.pairs::<Value, Value>() {
+            let (key, value) = pair?;
+            if !matches!(value, Value::Function(_))
+                && new.raw_get::<_, Value>(key.clone())?.is_nil()
+            {
+                new.raw_set(key, value)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub(super) fn reload_scripts(
+    mut commands: Commands,
+    mut asset_events: EventReader<AssetEvent<LuaScript>>,
+    scripts: Res<Assets<LuaScript>>,
+    asset_server: Res<AssetServer>,
+    card_database: Res<CardDatabase>,
+    query: Query<(Entity, &CardInstanceId, &CardInfo, &Script<LuaScript>)>,
+    mut diagnostics: EventWriter<ScriptDiagnostic>,
+    mut scripting_runtime: ResMut<LuaRuntime>,
+) {
+    for event in asset_events.read() {
+        let AssetEvent::Modified { id } = event else {
+            continue;
+        };
+        let Some(script) = scripts.get(*id) else {
+            continue;
+        };
+        let path = asset_server
+            .get_path(*id)
+            .map(|path| path.to_string())
+            .unwrap_or_default();
+        let mut count = 0;
+        scripting_runtime.with_engine_mut(|engine| {
+            // 有语法错误时 check_scripts 已经报告
+            let Ok(chunk) = engine
+                .load(script.0.as_str())
+                .set_name(path.as_str())
+                .into_function()
+            else {
+                return;
+            };
+            for (entity, card, card_info, card_script) in query.iter() {
+                if card_script.script.id() != *id {
+                    continue;
+                }
+                let result = reload_card(engine, &chunk, entity, *card)
+                    .and_then(|_| script_card_info(engine, *card));
+                match result {
+                    Ok(script_info) => {
+                        // 脚本中去掉了卡片信息时 恢复数据库中的信息
+                        let info =
+                            script_info.or_else(|| card_database.get(&card_info.id).cloned());
+                        let mut entity_commands = commands.entity(entity);
+                        entity_commands.remove::<BrokenCard>();
+                        if let Some(info) = info {
+                            entity_commands.insert(info);
+                        }
+                        count += 1;
+                    }
+                    Err(err) => {
+                        diagnostics.write(ScriptDiagnostic::new(*card, &card_info.id, err));
+                    }
+                }
+            }
+        });
+        info!("{} reloaded for {} cards", path, count);
+    }
+}
@@ -0,0 +1,224 @@
+use crate::lua::command::LuaCommands;
+use crate::lua::diagnostic::{BrokenCard, ScriptDiagnostic};
+use crate::lua::query::{GameView, location_name};
+use crate::lua::{ARCANE_CAST_FUNC, LuaRng, call_card_fn};
+use crate::match_state::CardInstanceId;
+use crate::rules::state::GameState;
+use crate::rules::{Action, CardId, Location, Phase, PlayerId, RuleEvent, ZoneKey};
+use crate::zone_info::ZoneType;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_scriptum::runtimes::lua::prelude::*;
+use mlua::{Lua, Table};
+use std::collections::HashSet;
+
+/// 效果触发
+/// 规则事件发生时调用卡片脚本中对应的方法 方法都是可选的
+/// 触发和脚本发起的效果在产生事件的操作之后立即结算 结算顺序只由操作的顺序决定
+/// 方法的参数是卡片自己的表和上下文 例如 function(self, ctx) print(ctx.card) end
+
+// 登场
+pub const ENTER_FUNC: &str = "on_enter";
+// 离开场上
+pub const LEAVE_FUNC: &str = "on_leave";
+// 宣言攻击
+pub const ATTACK_FUNC: &str = "on_attack";
+// 被破坏
+pub const DESTROYED_FUNC: &str = "on_destroyed";
+// 持有者的回合开始 只有场上的卡会触发
+pub const TURN_START_FUNC: &str = "on_turn_start";
+
+// 传给脚本的上下文 为空的字段不会出现在表中
+#[derive(Clone, Debug, Default)]
+pub struct EffectContext {
+    pub card: CardId,
+    pub player: u8,
+    pub turn: u32,
+    // 所在区域的类型和编号 和 Zones 使用的一致
+    pub zone: Option<ZoneKey>,
+    // 装备附着的人物
+    pub host: Option<CardId>,
+    // 攻击的对象或神秘术的目标
+    pub target: Option<CardId>,
+    // 离场时的去向
+    pub to: Option<Location>,
+}
+
+impl EffectContext {
+    fn new(card: CardId, player: PlayerId, match_state: &GameState) -> Self {
+        Self {
+            card,
+            player: player.0,
+            turn: match_state.turn,
+            ..Default::default()
+        }
+    }
+
+    fn to_table<'lua>(&self, engine: &'lua Lua) -> mlua::Result<Table<'lua>> {
+        let table = engine.create_table()?;
+        table.set("card", self.card)?;
+        table.set("player", self.player)?;
+        table.set("turn", self.turn)?;
+        if let Some(zone) = self.zone {
+            table.set("zone", format!("{:?}", ZoneType::from(zone)))?;
+            table.set("slot", zone.slot())?;
+        }
+        table.set("host", self.host)?;
+        table.set("target", self.target)?;
+        if let Some(to) = self.to {
+            table.set("to", location_name(to))?;
+        }
+        Ok(table)
+    }
+}
+
+// 规则事件对应的触发 一个事件可能触发多张卡
+fn triggers_of(event: &RuleEvent, match_state: &GameState) -> Vec<(&'static str, EffectContext)> {
+    let owner = |card: CardId| match_state.card(card).map(|c| c.owner).ok();
+    match *event {
+        RuleEvent::CardEntered { player, card, zone } => {
+            let mut context = EffectContext::new(card, player, match_state);
+            context.zone = Some(zone);
+            if let Ok(Location::Attached(_, host)) = match_state.card(card).map(|c| c.location) {
+                context.host = Some(host);
+            }
+            vec![(ENTER_FUNC, context)]
+        }
+        RuleEvent::CardMoved { card, from, to } if from.on_field() && !to.on_field() => {
+            let Some(player) = owner(card) else {
+                return vec![];
+            };
+            let mut context = EffectContext::new(card, player, match_state);
+            if let Location::Zone(_, key) = from {
+                context.zone = Some(key);
+            }
+            if let Location::Attached(_, host) = from {
+                context.host = Some(host);
+            }
+            context.to = Some(to);
+            vec![(LEAVE_FUNC, context)]
+        }
+        RuleEvent::AttackDeclared {
+            player,
+            attacker,
+            defender,
+        } => {
+            let mut context = EffectContext::new(attacker, player, match_state);
+            context.target = defender;
+            vec![(ATTACK_FUNC, context)]
+        }
+        RuleEvent::CardDestroyed { player, card } => {
+            vec![(
+                DESTROYED_FUNC,
+                EffectContext::new(card, player, match_state),
+            )]
+        }
+        RuleEvent::ArcaneCast {
+            player,
+            card,
+            target,
+        } => {
+            let mut context = EffectContext::new(card, player, match_state);
+            context.target = target;
+            vec![(ARCANE_CAST_FUNC, context)]
+        }
+        // 回合从抽卡阶段开始 按卡片编号的顺序触发
+        RuleEvent::PhaseStarted {
+            player,
+            phase: Phase::Draw,
+            ..
+        } => {
+            let mut cards: Vec<CardId> = match_state
+                .cards
+                .values()
+                .filter(|c| c.owner == player && c.location.on_field())
+                .map(|c| c.id)
+                .collect();
+            cards.sort();
+            cards
+                .into_iter()
+                .map(|card| {
+                    (
+                        TURN_START_FUNC,
+                        EffectContext::new(card, player, match_state),
+                    )
+                })
+                .collect()
+        }
+        _ => vec![],
+    }
+}
+
+// 结算触发需要的资源 规则核心接受一个操作后立即调用
+#[derive(SystemParam)]
+pub struct EffectTriggers<'w, 's> {
+    lua_rng: Res<'w, LuaRng>,
+    lua_commands: Res<'w, LuaCommands>,
+    game_view: Res<'w, GameView>,
+    diagnostics: EventWriter<'w, ScriptDiagnostic>,
+    query_broken: Query<'w, 's, &'static CardInstanceId, With<BrokenCard>>,
+    // 这一帧中脚本出错的卡片 帧结束后才会标记为损坏
+    failed: Local<'s, HashSet<CardId>>,
+    scripting_runtime: ResMut<'w, LuaRuntime>,
+}
+
+impl EffectTriggers<'_, '_> {
+    // 上一帧出错的卡片已经标记为损坏
+    pub fn begin_frame(&mut self) {
+        self.failed.clear();
+    }
+
+    // 调用一个操作的事件触发的方法 返回脚本发起的效果
+    // 脚本中的随机数和对局使用同一个序列
+    pub fn run(&mut self, events: &[RuleEvent], match_state: &mut GameState) -> Vec<Action> {
+        let triggers: Vec<_> = events
+            .iter()
+            .flat_map(|event| triggers_of(event, match_state))
+            .collect();
+        if triggers.is_empty() {
+            return vec![];
+        }
+        // 脚本查询到的是这个操作结算后的状态
+        *self.game_view.0.lock().unwrap() = match_state.clone();
+        // 损坏的卡片不再调用脚本
+        let broken: HashSet<CardId> = self.query_broken.iter().map(|card| card.0).collect();
+        let Self {
+            lua_rng,
+            lua_commands,
+            diagnostics,
+            failed,
+            scripting_runtime,
+            ..
+        } = self;
+        lua_rng.lend(match_state.rng);
+        scripting_runtime.with_engine_mut(|engine| {
+            for (name, context) in triggers {
+                if broken.contains(&context.card) || failed.contains(&context.card) {
+                    continue;
+                }
+                let card = CardInstanceId(context.card);
+                lua_commands.begin(context.card);
+                let result = context
+                    .to_table(engine)
+                    .and_then(|ctx| call_card_fn::<_, ()>(engine, card, name, ctx));
+                if let Err(err) = result {
+                    failed.insert(context.card);
+                    let id = match_state
+                        .card_info(context.card)
+                        .map(|info| info.id.clone())
+                        .unwrap_or_default();
+                    diagnostics.write(ScriptDiagnostic::new(
+                        card,
+                        &id,
+                        format!("{} failed: {}", name, err),
+                    ));
+                }
+                lua_commands.end();
+            }
+        });
+        if let Some(rng) = lua_rng.take() {
+            match_state.rng = rng;
+        }
+        lua_commands.drain()
+    }
+}
@@ -1,14 +1,25 @@
+mod asset_dir;
+mod battle;
 pub mod card_info;
 mod card_zone;
 mod debug_lab;
+mod deck;
 mod game;
 mod hand_card;
 mod lua;
+mod match_state;
+mod replay;
+mod rules;
+mod snapshot;
+mod turn;
 mod ui;
 mod zone_info;
+mod zone_layout;
 
+use crate::asset_dir::ASSET_DIR;
 use crate::game::GamePlugin;
 use crate::hand_card::CardLineResource;
+use crate::replay::{Replay, ReplayPlayer};
 use bevy::asset::load_internal_binary_asset;
 use bevy::prelude::*;
 use bevy_card3d_kit::prelude::*;
@@ -17,13 +28,46 @@ use bevy_inspector_egui::egui::TextStyle;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 
 fn main() {
+    // 命令行检查牌组 validate-deck <deck file> [format]
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("validate-deck") {
+        std::process::exit(deck::validate::run_cli(&args[1..]));
+    }
+
+    // 回放录像 replay <replay file>
+    let replay = if args.first().map(String::as_str) == Some("replay") {
+        let Some(path) = args.get(1) else {
+            eprintln!("usage: replay <replay file>");
+            std::process::exit(2);
+        };
+        match Replay::load(path) {
+            Ok(replay) => Some(replay),
+            Err(err) => {
+                eprintln!("failed to load replay: {}", err);
+                std::process::exit(2);
+            }
+        }
+    } else {
+        None
+    };
+
     let mut app = App::new();
-    app.add_plugins((DefaultPlugins, Card3DPlugins, GamePlugin))
-        .add_plugins(EguiPlugin {
-            enable_multipass_for_primary_context: true,
-        })
-        .add_plugins(WorldInspectorPlugin::new())
-        .add_systems(Startup, setup);
+    if let Some(replay) = replay {
+        app.insert_resource(ReplayPlayer::new(replay));
+    }
+    app.add_plugins((
+        DefaultPlugins.set(AssetPlugin {
+            file_path: ASSET_DIR.to_string(),
+            ..default()
+        }),
+        Card3DPlugins,
+        GamePlugin,
+    ))
+    .add_plugins(EguiPlugin {
+        enable_multipass_for_primary_context: true,
+    })
+    .add_plugins(WorldInspectorPlugin::new())
+    .add_systems(Startup, setup);
 
     // 加载默认字体
     load_internal_binary_asset!(
@@ -0,0 +1,303 @@
+use crate::card_info::CardInfo;
+use crate::deck::DeckLegality;
+use crate::hand_card::CardLineResource;
+use crate::lua::trigger::EffectTriggers;
+use crate::rules::state::GameState;
+use crate::rules::{Action, CardId, Location, PlayerId, RuleEvent, ZoneKey};
+use crate::zone_info::ZoneRegistry;
+use bevy::ecs::component::HookContext;
+use bevy::ecs::world::DeferredWorld;
+use bevy::prelude::*;
+use bevy_card3d_kit::highlight::Highlight;
+use bevy_card3d_kit::prelude::card_state::{CardState, ChangeCardState};
+use bevy_card3d_kit::prelude::{CardLine, HandCard, HandCardChanged, Moveable};
+use bevy_card3d_kit::zone::desk_zone::{DeskCard, DeskZone, DeskZoneChangedEvent};
+use std::collections::{HashMap, VecDeque};
+
+/// 对局状态
+/// 规则核心是唯一的数据来源 这里只负责把规则事件同步到 ECS
+
+#[derive(Resource, Default, Debug, Deref, DerefMut)]
+pub struct MatchState(pub GameState);
+
+// 卡片在对局内的编号 生成卡片时分配 和规则核心的 CardId 相同
+// 实体会因为存档读取而变化 对话框 脚本 日志和录像都使用这个编号
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash, Reflect)]
+#[component(on_insert = on_insert_card_instance, on_replace = on_replace_card_instance)]
+pub struct CardInstanceId(pub CardId);
+
+impl std::fmt::Display for CardInstanceId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "card#{}", self.0)
+    }
+}
+
+// 编号挂到实体上时登记对应关系
+fn on_insert_card_instance(mut world: DeferredWorld, context: HookContext) {
+    if let Some(card) = world.get::<CardInstanceId>(context.entity).copied() {
+        world
+            .resource_mut::<CardEntityMap>()
+            .insert(card.0, context.entity);
+    }
+}
+
+// 实体销毁或编号被替换时移除
+fn on_replace_card_instance(mut world: DeferredWorld, context: HookContext) {
+    world.resource_mut::<CardEntityMap>().remove(context.entity);
+}
+
+// 规则核心的卡片和实体的对应关系 由 CardInstanceId 维护
+#[derive(Resource, Default, Debug)]
+pub struct CardEntityMap {
+    by_card: HashMap<CardId, Entity>,
+    by_entity: HashMap<Entity, CardId>,
+}
+
+impl CardEntityMap {
+    fn insert(&mut self, card: CardId, entity: Entity) {
+        self.by_card.insert(card, entity);
+        self.by_entity.insert(entity, card);
+    }
+
+    pub fn entity(&self, card: CardId) -> Option<Entity> {
+        self.by_card.get(&card).copied()
+    }
+
+    pub fn card(&self, entity: Entity) -> Option<CardId> {
+        self.by_entity.get(&entity).copied()
+    }
+
+    fn remove(&mut self, entity: Entity) {
+        if let Some(card) = self.by_entity.remove(&entity) {
+            // 同一张卡可能已经对应到新的实体
+            if self.by_card.get(&card) == Some(&entity) {
+                self.by_card.remove(&card);
+            }
+        }
+    }
+}
+
+// 提交给规则核心的操作
+#[derive(Event, Clone, Debug)]
+pub struct GameAction(pub Action);
+
+// 规则核心执行后产生的事件
+#[derive(Event, Clone, Debug)]
+pub struct MatchEvent(pub RuleEvent);
+
+// 规则核心接受的操作 被拒绝的操作不会发出
+#[derive(Event, Clone, Debug)]
+pub struct ActionAccepted(pub Action);
+
+pub struct MatchStatePlugin;
+
+impl Plugin for MatchStatePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(MatchState(GameState::with_seed(match_seed())));
+        app.init_resource::<CardEntityMap>();
+        app.register_type::<CardInstanceId>();
+        app.add_event::<GameAction>();
+        app.add_event::<MatchEvent>();
+        app.add_event::<ActionAccepted>();
+        app.add_systems(PostStartup, init_board);
+        app.add_systems(
+            Update,
+            (sync_card_info, apply_game_actions, mirror_match_events).chain(),
+        );
+    }
+}
+
+// 环境变量 MATCH_SEED 指定随机数种子 没有时使用当前时间
+fn match_seed() -> u64 {
+    let seed = std::env::var("MATCH_SEED")
+        .ok()
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or_default()
+        });
+    info!("match seed {}", seed);
+    seed
+}
+
+// 规则核心使用布局中的区域
+fn init_board(zone_registry: Res<ZoneRegistry>, mut match_state: ResMut<MatchState>) {
+    for player in PlayerId::ALL {
+        if let Err(err) = match_state.set_board(player, &zone_registry.board(player)) {
+            warn!("init board of {:?} failed: {}", player, err);
+        }
+    }
+}
+
+// 脚本加载出卡片信息后同步到规则核心 脚本重新加载后也会同步
+fn sync_card_info(
+    query: Query<(Entity, &CardInfo), Changed<CardInfo>>,
+    card_entity_map: Res<CardEntityMap>,
+    mut match_state: ResMut<MatchState>,
+) {
+    for (entity, card_info) in query.iter() {
+        if let Some(card) = card_entity_map.card(entity) {
+            if let Err(err) = match_state.set_card_info(card, card_info.clone()) {
+                warn!("sync card info failed: {}", err);
+            }
+        }
+    }
+}
+
+// 一个操作连锁发起的效果数量上限 防止脚本互相触发无法结束
+const MAX_CHAINED_EFFECTS: usize = 256;
+
+// 操作被接受后立即结算它触发的效果 结算顺序只由操作的顺序决定 和帧无关
+fn apply_game_actions(
+    mut actions: EventReader<GameAction>,
+    deck_legality: Res<DeckLegality>,
+    mut match_state: ResMut<MatchState>,
+    mut match_events: EventWriter<MatchEvent>,
+    mut accepted: EventWriter<ActionAccepted>,
+    mut triggers: EffectTriggers,
+) {
+    triggers.begin_frame();
+    for action in actions.read() {
+        // 有不合法的牌组时不能开始对局
+        if let Action::StartMatch { .. } = action.0 {
+            if !deck_legality.can_start() {
+                warn!("match cannot start: {:?}", deck_legality.illegal);
+                continue;
+            }
+        }
+        let mut pending = VecDeque::from([action.0.clone()]);
+        let mut chained = 0;
+        while let Some(action) = pending.pop_front() {
+            match match_state.apply(action.clone()) {
+                Ok(events) => {
+                    for effect in triggers.run(&events, &mut match_state.0) {
+                        if chained >= MAX_CHAINED_EFFECTS {
+                            warn!("too many chained effects, dropped {:?}", effect);
+                            continue;
+                        }
+                        chained += 1;
+                        pending.push_back(effect);
+                    }
+                    accepted.write(ActionAccepted(action));
+                    for event in events {
+                        match_events.write(MatchEvent(event));
+                    }
+                }
+                Err(err) => {
+                    warn!("action {:?} rejected: {}", action, err);
+                }
+            }
+        }
+    }
+}
+
+// 把规则事件同步到卡片实体上
+fn mirror_match_events(
+    mut commands: Commands,
+    mut match_events: EventReader<MatchEvent>,
+    card_entity_map: Res<CardEntityMap>,
+    zone_registry: Res<ZoneRegistry>,
+    card_line_resource: Res<CardLineResource>,
+    mut query_desks: Query<&mut DeskZone>,
+    mut query_card_line: Query<&mut CardLine>,
+    mut desk_card_event: EventWriter<DeskZoneChangedEvent>,
+    mut hand_card_event: EventWriter<HandCardChanged>,
+) {
+    for event in match_events.read() {
+        match &event.0 {
+            &RuleEvent::CardMoved { card, from, to } => {
+                let Some(card_entity) = card_entity_map.entity(card) else {
+                    continue;
+                };
+                match from {
+                    Location::Hand(player) => {
+                        let card_line_entity = card_line_resource.card_line(player);
+                        if let Ok(mut card_line) = query_card_line.get_mut(card_line_entity) {
+                            card_line.card_list.retain(|e| *e != card_entity);
+                        }
+                        hand_card_event.write(HandCardChanged::Remove {
+                            card_entity,
+                            card_line_entity,
+                        });
+                        // FIXME: 此处移动层面不一样
+                        commands.entity(card_entity).remove::<HandCard>();
+                    }
+                    Location::Zone(player, key) => {
+                        if let Some(desk) = zone_registry.zone(player, key) {
+                            if let Ok(mut desk_zone) = query_desks.get_mut(desk) {
+                                desk_zone.card_list.retain(|e| *e != card_entity);
+                            }
+                            desk_card_event.write(DeskZoneChangedEvent::Removed {
+                                desk,
+                                card: card_entity,
+                            });
+                        }
+                        if let Location::Zone(_, to_key) = to {
+                            commands
+                                .entity(card_entity)
+                                .remove::<Highlight>()
+                                .remove::<DeskCard>()
+                                .remove::<CardState>();
+                            // 没有默认状态的区域 正面竖直放置
+                            if to_key.default_pose().is_none() {
+                                commands.entity(card_entity).insert(CardState {
+                                    face_up: true,
+                                    vertical: true,
+                                });
+                            }
+                        } else if let Location::Attached(..) = to {
+                            commands
+                                .entity(card_entity)
+                                .remove::<Highlight>()
+                                .remove::<DeskCard>();
+                        }
+                    }
+                    // 装备的关系由 AttachPlugin 处理
+                    Location::Attached(..) => {}
+                }
+                match to {
+                    Location::Hand(player) => {
+                        commands
+                            .entity(card_entity)
+                            .insert(HandCard {
+                                belong_to_card_line: Some(card_line_resource.card_line(player)),
+                            })
+                            .insert(Moveable);
+                    }
+                    Location::Zone(player, key) => {
+                        commands.entity(card_entity).insert(DeskCard {
+                            belongs_to_desk: zone_registry.zone(player, key),
+                        });
+                    }
+                    Location::Attached(..) => {}
+                }
+            }
+            &RuleEvent::CardStood { card } => {
+                if let Some(card_entity) = card_entity_map.entity(card) {
+                    commands
+                        .entity(card_entity)
+                        .insert(ChangeCardState(CardState {
+                            face_up: true,
+                            vertical: true,
+                        }));
+                }
+            }
+            // 按规则核心的顺序重新排列卡组
+            RuleEvent::DeskShuffled { player, order } => {
+                let Some(desk) = zone_registry.zone(*player, ZoneKey::Desk) else {
+                    continue;
+                };
+                if let Ok(mut desk_zone) = query_desks.get_mut(desk) {
+                    desk_zone.card_list = order
+                        .iter()
+                        .filter_map(|card| card_entity_map.entity(*card))
+                        .collect();
+                }
+            }
+            // 回合相关的事件由 TurnPlugin 处理
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,344 @@
+use crate::debug_lab::spawn_button;
+use crate::deck::{DeckLoaded, LoadDeck};
+use crate::match_state::{ActionAccepted, GameAction, MatchState};
+use crate::rules::{Action, PlayerId};
+use bevy::color;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// 对局录像
+/// 记录随机数种子和每一个操作 回放时按顺序重新提交 同样的种子得到同样的对局
+/// 回放 cargo run -- replay replays/latest.replay.ron
+
+// 默认的录像文件 环境变量 REPLAY_RECORD 可以指定
+pub const DEFAULT_REPLAY: &str = "replays/latest.replay.ron";
+// 自动播放时每一步的间隔
+const STEP_SECONDS: f32 = 0.5;
+// 录制时写入文件的间隔 退出时也会写入
+const SAVE_SECONDS: f32 = 2.0;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ReplayStep {
+    // 牌组放入卡组 路径相对 assets
+    LoadDeck { player: PlayerId, deck: String },
+    Action(Action),
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    pub steps: Vec<ReplayStep>,
+}
+
+impl Replay {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        ron::from_str(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        if let Some(dir) = Path::new(path).parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", path, e))?;
+        }
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| format!("{}: {}", path, e))?;
+        std::fs::write(path, text).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+// 录制中的录像 定时写入文件 程序崩溃时最多丢失最后几秒
+#[derive(Resource, Debug)]
+pub struct ReplayRecorder {
+    pub path: String,
+    pub replay: Replay,
+    // 有还没有写入文件的步骤
+    pub dirty: bool,
+    // 读取存档后停止录制 录像只能从对局开始回放
+    pub recording: bool,
+}
+
+impl Default for ReplayRecorder {
+    fn default() -> Self {
+        Self {
+            path: std::env::var("REPLAY_RECORD").unwrap_or_else(|_| DEFAULT_REPLAY.to_string()),
+            replay: Replay::default(),
+            dirty: false,
+            recording: true,
+        }
+    }
+}
+
+impl ReplayRecorder {
+    // 写入已经录制的部分后停止录制
+    pub fn stop(&mut self) {
+        if self.dirty {
+            if let Err(err) = self.replay.save(&self.path) {
+                warn!("save replay failed: {}", err);
+            }
+        }
+        self.replay = Replay::default();
+        self.dirty = false;
+        self.recording = false;
+    }
+}
+
+// 回放中的录像 存在时不再录制 也不会自动推进阶段
+#[derive(Resource, Debug)]
+pub struct ReplayPlayer {
+    pub replay: Replay,
+    // 下一步的位置
+    pub cursor: usize,
+    pub playing: bool,
+    // 单步执行
+    step_once: bool,
+    // 等待牌组放入卡组后再继续
+    waiting_deck: Option<PlayerId>,
+    timer: Timer,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            cursor: 0,
+            playing: false,
+            step_once: false,
+            waiting_deck: None,
+            timer: Timer::from_seconds(STEP_SECONDS, TimerMode::Repeating),
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.cursor >= self.replay.steps.len()
+    }
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct ReplayText;
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReplayRecorder>();
+        app.add_systems(
+            Startup,
+            start_replay.run_if(resource_exists::<ReplayPlayer>),
+        );
+        app.add_systems(
+            Update,
+            (
+                record_steps.run_if(not(resource_exists::<ReplayPlayer>)),
+                (play_replay, update_replay_text)
+                    .chain()
+                    .run_if(resource_exists::<ReplayPlayer>),
+            ),
+        );
+        app.add_systems(
+            Last,
+            save_replay.run_if(not(resource_exists::<ReplayPlayer>)),
+        );
+    }
+}
+
+// 使用录像的种子 并显示播放控制
+fn start_replay(
+    mut commands: Commands,
+    replay_player: Res<ReplayPlayer>,
+    mut match_state: ResMut<MatchState>,
+) {
+    if let Err(err) = match_state.reseed(replay_player.replay.seed) {
+        warn!("reseed for replay failed: {}", err);
+    }
+    info!(
+        "replay seed {} with {} steps",
+        replay_player.replay.seed,
+        replay_player.replay.steps.len()
+    );
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::FlexEnd,
+                align_items: AlignItems::FlexEnd,
+                ..Default::default()
+            },
+            Pickable::IGNORE,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    Node {
+                        width: Val::Px(200.0),
+                        height: Val::Auto,
+                        flex_direction: FlexDirection::Column,
+                        padding: UiRect::all(Val::Px(2.0)),
+                        ..Default::default()
+                    },
+                    BackgroundColor(color::palettes::css::DARK_GRAY.into()),
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        ReplayText,
+                        Text::new(""),
+                        TextFont {
+                            font_size: 12.0,
+                            ..default()
+                        },
+                        TextColor(Color::WHITE),
+                    ));
+                    spawn_button(parent, "play/pause".to_string(), on_click_play);
+                    spawn_button(parent, "step".to_string(), on_click_step);
+                });
+        });
+}
+
+fn on_click_play(_click: Trigger<Pointer<Click>>, mut replay_player: ResMut<ReplayPlayer>) {
+    replay_player.playing = !replay_player.playing;
+}
+
+fn on_click_step(_click: Trigger<Pointer<Click>>, mut replay_player: ResMut<ReplayPlayer>) {
+    replay_player.playing = false;
+    replay_player.step_once = true;
+}
+
+// 记录放入卡组的牌组和规则核心接受的操作
+// 自动推进阶段的操作也会记录 回放时不再自动推进
+fn record_steps(
+    mut recorder: ResMut<ReplayRecorder>,
+    match_state: Res<MatchState>,
+    asset_server: Res<AssetServer>,
+    mut deck_loaded: EventReader<DeckLoaded>,
+    mut actions: EventReader<ActionAccepted>,
+) {
+    if !recorder.recording {
+        return;
+    }
+    let count = recorder.replay.steps.len();
+    for loaded in deck_loaded.read() {
+        let Some(path) = asset_server.get_path(&loaded.deck) else {
+            warn!("deck of {:?} has no path, not recorded", loaded.player);
+            continue;
+        };
+        recorder.replay.steps.push(ReplayStep::LoadDeck {
+            player: loaded.player,
+            deck: path.to_string(),
+        });
+    }
+    for action in actions.read() {
+        // 回放时卡片效果在同一个操作中重新结算
+        if let Action::Effect { .. } = action.0 {
+            continue;
+        }
+        recorder
+            .replay
+            .steps
+            .push(ReplayStep::Action(action.0.clone()));
+    }
+    if recorder.replay.steps.len() == count {
+        return;
+    }
+    recorder.replay.seed = match_state.seed;
+    recorder.dirty = true;
+}
+
+// 每隔一段时间和退出时把录像写入文件
+fn save_replay(
+    time: Res<Time>,
+    mut recorder: ResMut<ReplayRecorder>,
+    mut app_exit: EventReader<AppExit>,
+    mut saved_at: Local<f32>,
+) {
+    let exiting = app_exit.read().count() > 0;
+    if !recorder.dirty || (!exiting && time.elapsed_secs() - *saved_at < SAVE_SECONDS) {
+        return;
+    }
+    *saved_at = time.elapsed_secs();
+    recorder.dirty = false;
+    if let Err(err) = recorder.replay.save(&recorder.path) {
+        warn!("save replay failed: {}", err);
+    }
+}
+
+// 空格 播放/暂停 右方向键 单步
+fn play_replay(
+    time: Res<Time>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    asset_server: Res<AssetServer>,
+    mut replay_player: ResMut<ReplayPlayer>,
+    mut deck_loaded: EventReader<DeckLoaded>,
+    mut load_deck: EventWriter<LoadDeck>,
+    mut actions: EventWriter<GameAction>,
+) {
+    for loaded in deck_loaded.read() {
+        if replay_player.waiting_deck == Some(loaded.player) {
+            replay_player.waiting_deck = None;
+        }
+    }
+    if keyboard.just_pressed(KeyCode::Space) {
+        replay_player.playing = !replay_player.playing;
+    }
+    if keyboard.just_pressed(KeyCode::ArrowRight) {
+        replay_player.playing = false;
+        replay_player.step_once = true;
+    }
+    let mut step = std::mem::take(&mut replay_player.step_once);
+    if replay_player.playing {
+        replay_player.timer.tick(time.delta());
+        step |= replay_player.timer.just_finished();
+    }
+    if !step || replay_player.waiting_deck.is_some() {
+        return;
+    }
+    let Some(next) = replay_player
+        .replay
+        .steps
+        .get(replay_player.cursor)
+        .cloned()
+    else {
+        replay_player.playing = false;
+        return;
+    };
+    replay_player.cursor += 1;
+    match next {
+        ReplayStep::LoadDeck { player, deck } => {
+            load_deck.write(LoadDeck {
+                player,
+                deck: asset_server.load(deck),
+            });
+            replay_player.waiting_deck = Some(player);
+        }
+        ReplayStep::Action(action) => {
+            actions.write(GameAction(action));
+        }
+    }
+}
+
+fn update_replay_text(
+    replay_player: Res<ReplayPlayer>,
+    mut query: Query<&mut Text, With<ReplayText>>,
+) {
+    if !replay_player.is_changed() {
+        return;
+    }
+    let state = if replay_player.finished() {
+        "finished"
+    } else if replay_player.waiting_deck.is_some() {
+        "loading deck"
+    } else if replay_player.playing {
+        "playing"
+    } else {
+        "paused"
+    };
+    for mut text in query.iter_mut() {
+        text.0 = format!(
+            "replay {}/{} {}\nspace: play/pause  right: step",
+            replay_player.cursor,
+            replay_player.replay.steps.len(),
+            state
+        );
+    }
+}
@@ -0,0 +1,234 @@
+/// 登场费用的计算
+/// 费用 = 手卡放入理性区的张数 + 激情区送去墓地的张数
+/// 手卡支付的张数不能超过手卡数量和理性区空位
+
+// 费用修正
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CostModifier {
+    // 费用增减 结果最小为 0
+    pub cost_delta: isize,
+    // 不能用手卡支付
+    pub no_hand: bool,
+    // 不能用激情支付
+    pub no_jq: bool,
+}
+
+// 某个来源可以支付的张数范围
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CostBound {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl CostBound {
+    pub fn contains(&self, n: usize) -> bool {
+        n >= self.min && n <= self.max
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CostSolution {
+    // 修正后的费用
+    pub cost: usize,
+    pub feasible: bool,
+    pub hand: CostBound,
+    pub jq: CostBound,
+}
+
+impl CostSolution {
+    // 这种支付方式是否合法
+    pub fn accepts(&self, hand: usize, jq: usize) -> bool {
+        self.feasible && hand + jq == self.cost && self.hand.contains(hand) && self.jq.contains(jq)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CostSolver {
+    pub cost: usize,
+    // 可以用来支付的手卡数量 不包含要登场的卡
+    pub hand: usize,
+    // 理性区空位
+    pub lx_free: usize,
+    // 激情区卡片数量
+    pub jq: usize,
+    pub modifiers: Vec<CostModifier>,
+}
+
+impl CostSolver {
+    pub fn new(cost: usize, hand: usize, lx_free: usize, jq: usize) -> Self {
+        Self {
+            cost,
+            hand,
+            lx_free,
+            jq,
+            modifiers: vec![],
+        }
+    }
+
+    pub fn with_modifier(mut self, modifier: CostModifier) -> Self {
+        self.modifiers.push(modifier);
+        self
+    }
+
+    pub fn solve(&self) -> CostSolution {
+        let delta: isize = self.modifiers.iter().map(|m| m.cost_delta).sum();
+        let cost = self.cost.saturating_add_signed(delta);
+        let hand_cap = if self.modifiers.iter().any(|m| m.no_hand) {
+            0
+        } else {
+            self.hand.min(self.lx_free)
+        };
+        let jq_cap = if self.modifiers.iter().any(|m| m.no_jq) {
+            0
+        } else {
+            self.jq
+        };
+        let hand_max = cost.min(hand_cap);
+        let jq_max = cost.min(jq_cap);
+        CostSolution {
+            cost,
+            feasible: cost <= hand_cap + jq_cap,
+            hand: CostBound {
+                min: cost.saturating_sub(jq_max).min(hand_max),
+                max: hand_max,
+            },
+            jq: CostBound {
+                min: cost.saturating_sub(hand_max).min(jq_max),
+                max: jq_max,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 穷举所有支付方式
+    fn brute_force(solver: &CostSolver) -> Vec<(usize, usize)> {
+        let solution = solver.solve();
+        let no_hand = solver.modifiers.iter().any(|m| m.no_hand);
+        let no_jq = solver.modifiers.iter().any(|m| m.no_jq);
+        let mut result = vec![];
+        for hand in 0..=solver.hand {
+            for jq in 0..=solver.jq {
+                if hand + jq != solution.cost || hand > solver.lx_free {
+                    continue;
+                }
+                if (no_hand && hand > 0) || (no_jq && jq > 0) {
+                    continue;
+                }
+                result.push((hand, jq));
+            }
+        }
+        result
+    }
+
+    fn check(solver: CostSolver) {
+        let solution = solver.solve();
+        let pairs = brute_force(&solver);
+        assert_eq!(solution.feasible, !pairs.is_empty(), "{:?}", solver);
+        if pairs.is_empty() {
+            return;
+        }
+        let hand_min = pairs.iter().map(|p| p.0).min().unwrap();
+        let hand_max = pairs.iter().map(|p| p.0).max().unwrap();
+        let jq_min = pairs.iter().map(|p| p.1).min().unwrap();
+        let jq_max = pairs.iter().map(|p| p.1).max().unwrap();
+        assert_eq!(
+            solution.hand,
+            CostBound {
+                min: hand_min,
+                max: hand_max
+            },
+            "{:?}",
+            solver
+        );
+        assert_eq!(
+            solution.jq,
+            CostBound {
+                min: jq_min,
+                max: jq_max
+            },
+            "{:?}",
+            solver
+        );
+        for hand in 0..=solver.hand + 1 {
+            for jq in 0..=solver.jq + 1 {
+                assert_eq!(
+                    solution.accepts(hand, jq),
+                    pairs.contains(&(hand, jq)),
+                    "{:?} {} {}",
+                    solver,
+                    hand,
+                    jq
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn exhaustive_small_inputs() {
+        for cost in 0..=8 {
+            for hand in 0..=8 {
+                for lx_free in 0..=6 {
+                    for jq in 0..=6 {
+                        check(CostSolver::new(cost, hand, lx_free, jq));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn exhaustive_with_modifiers() {
+        let modifiers = [
+            CostModifier {
+                cost_delta: -2,
+                ..Default::default()
+            },
+            CostModifier {
+                cost_delta: 1,
+                ..Default::default()
+            },
+            CostModifier {
+                no_hand: true,
+                ..Default::default()
+            },
+            CostModifier {
+                no_jq: true,
+                ..Default::default()
+            },
+        ];
+        for modifier in modifiers {
+            for cost in 0..=6 {
+                for hand in 0..=6 {
+                    for lx_free in 0..=6 {
+                        for jq in 0..=6 {
+                            check(CostSolver::new(cost, hand, lx_free, jq).with_modifier(modifier));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn zero_cost_needs_nothing() {
+        let solution = CostSolver::new(0, 0, 0, 0).solve();
+        assert!(solution.feasible);
+        assert!(solution.accepts(0, 0));
+    }
+
+    #[test]
+    fn cost_reduction_never_underflows() {
+        let solution = CostSolver::new(1, 0, 0, 0)
+            .with_modifier(CostModifier {
+                cost_delta: -5,
+                ..Default::default()
+            })
+            .solve();
+        assert_eq!(solution.cost, 0);
+        assert!(solution.feasible);
+    }
+}
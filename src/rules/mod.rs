@@ -0,0 +1,460 @@
+pub mod cost;
+pub mod rng;
+pub mod state;
+
+/// 规则核心
+/// 不依赖 ECS 的对局模型，Bevy 的系统只负责把这里产生的事件同步到画面上
+use crate::card_info::CardInfo;
+use crate::card_info::card_enums::CardType;
+use bevy::prelude::Component;
+use serde::{Deserialize, Serialize};
+
+// 初始生命
+pub const STARTING_LIFE: u32 = 4000;
+
+// 玩家
+// 同时作为组件挂在场地 手卡和卡片上 表示归属
+#[derive(
+    Component, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct PlayerId(pub u8);
+
+impl PlayerId {
+    pub const MY: PlayerId = PlayerId(0);
+    pub const OPPONENT: PlayerId = PlayerId(1);
+
+    pub const ALL: [PlayerId; 2] = [PlayerId::MY, PlayerId::OPPONENT];
+
+    // 对手
+    pub fn other(self) -> PlayerId {
+        if self == PlayerId::MY {
+            PlayerId::OPPONENT
+        } else {
+            PlayerId::MY
+        }
+    }
+}
+
+// 对局内的卡片编号
+pub type CardId = u32;
+
+// 回合阶段
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Phase {
+    // 抽卡
+    Draw,
+    // 理性区回复
+    Recovery,
+    // 主要阶段
+    Main,
+    // 战斗
+    Battle,
+    // 结束
+    End,
+}
+
+impl Phase {
+    pub fn next(self) -> Phase {
+        match self {
+            Phase::Draw => Phase::Recovery,
+            Phase::Recovery => Phase::Main,
+            Phase::Main => Phase::Battle,
+            Phase::Battle => Phase::End,
+            Phase::End => Phase::Draw,
+        }
+    }
+}
+
+// 对局结束的原因
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GameOverReason {
+    // 生命归零
+    LifeZero,
+    // 卡组抽完
+    DeckOut,
+}
+
+// 场地的键 和场地注册表中的实体一一对应
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ZoneKey {
+    Desk,
+    Grave,
+    Lx,
+    Jq,
+    // 战场 编号从 1 开始 数量由棋盘决定
+    Battle(usize),
+    Prepare,
+    // 安全屋 编号从 1 开始
+    Safe(usize),
+}
+
+impl ZoneKey {
+    // 默认棋盘的所有区域
+    pub const ALL: [ZoneKey; 12] = [
+        ZoneKey::Desk,
+        ZoneKey::Grave,
+        ZoneKey::Lx,
+        ZoneKey::Jq,
+        ZoneKey::Battle(1),
+        ZoneKey::Battle(2),
+        ZoneKey::Battle(3),
+        ZoneKey::Prepare,
+        ZoneKey::Safe(1),
+        ZoneKey::Safe(2),
+        ZoneKey::Safe(3),
+        ZoneKey::Safe(4),
+    ];
+
+    // 战场和安全屋的编号
+    pub fn slot(&self) -> Option<usize> {
+        match self {
+            ZoneKey::Battle(slot) | ZoneKey::Safe(slot) => Some(*slot),
+            _ => None,
+        }
+    }
+
+    // 场上的区域 离开时装备的模因会送去墓地
+    pub fn is_field(&self) -> bool {
+        matches!(
+            self,
+            ZoneKey::Battle(_) | ZoneKey::Prepare | ZoneKey::Safe(_)
+        )
+    }
+
+    // 进入区域后卡片的默认状态
+    pub fn default_pose(&self) -> Option<CardPose> {
+        match self {
+            ZoneKey::Desk => Some(CardPose {
+                face_up: false,
+                vertical: true,
+            }),
+            ZoneKey::Lx => Some(CardPose {
+                face_up: true,
+                vertical: false,
+            }),
+            ZoneKey::Jq => Some(CardPose {
+                face_up: false,
+                vertical: false,
+            }),
+            _ => None,
+        }
+    }
+}
+
+// 区域的容量和可以登场的卡片类型 来自棋盘布局
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ZoneRule {
+    // None 为不限
+    pub capacity: Option<usize>,
+    pub accepts: Vec<CardType>,
+}
+
+// 卡片所在的位置
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Location {
+    Hand(PlayerId),
+    Zone(PlayerId, ZoneKey),
+    // 作为装备附着在人物上
+    Attached(PlayerId, CardId),
+}
+
+impl Location {
+    pub fn player(&self) -> PlayerId {
+        match self {
+            Location::Hand(player) => *player,
+            Location::Zone(player, _) => *player,
+            Location::Attached(player, _) => *player,
+        }
+    }
+
+    // 在场上 包括附着在人物上的装备
+    pub fn on_field(&self) -> bool {
+        match self {
+            Location::Zone(_, key) => key.is_field(),
+            Location::Attached(..) => true,
+            Location::Hand(_) => false,
+        }
+    }
+}
+
+// 卡片的朝向 对应 CardState
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CardPose {
+    pub face_up: bool,
+    pub vertical: bool,
+}
+
+// 对局中的一张卡
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CardInstance {
+    pub id: CardId,
+    pub owner: PlayerId,
+    // 脚本加载完成之前为空
+    pub info: Option<CardInfo>,
+    pub location: Location,
+    pub pose: CardPose,
+}
+
+// 效果持续的时间
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EffectDuration {
+    // 到本回合结束
+    EndOfTurn,
+    // 持续若干个回合 包括当前回合
+    Turns(u32),
+    Permanent,
+}
+
+// 卡片效果 由脚本发起 不检查阶段和回合
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Effect {
+    Draw {
+        player: PlayerId,
+        count: usize,
+    },
+    // 移动到指定位置 场上的区域需要检查容量和卡片类型
+    MoveCard {
+        card: CardId,
+        to: Location,
+    },
+    // 破坏场上的卡
+    Destroy {
+        card: CardId,
+    },
+    ModifyAck {
+        card: CardId,
+        delta: i32,
+        duration: EffectDuration,
+    },
+    // 随机丢弃手卡
+    Discard {
+        player: PlayerId,
+        count: usize,
+    },
+    // 从卡组中把一张卡加入手卡 之后洗切卡组
+    SearchDeck {
+        player: PlayerId,
+        card: CardId,
+    },
+}
+
+// 攻击力的增减
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AckModifier {
+    pub card: CardId,
+    pub delta: i32,
+    // 这个回合结束时失效 为空时一直有效
+    pub until_turn: Option<u32>,
+}
+
+// 玩家的操作 会记录到录像中
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Action {
+    // 从卡组抽一张
+    Draw {
+        player: PlayerId,
+    },
+    // 手卡放入理性区
+    PutHandInLx {
+        player: PlayerId,
+        card: CardId,
+    },
+    // 卡组顶放入激情区
+    PutDeskInJq {
+        player: PlayerId,
+    },
+    // 理性区回复 竖直的回到手卡 横置的竖直
+    RecoverLx {
+        player: PlayerId,
+    },
+    // 洗切卡组
+    Shuffle {
+        player: PlayerId,
+    },
+    // 开始对局 洗切双方卡组后先手玩家进入抽卡阶段
+    StartMatch {
+        first: PlayerId,
+    },
+    // 结束当前阶段
+    AdvancePhase {
+        player: PlayerId,
+    },
+    // 登场 手卡放入理性区 激情区的卡送去墓地 作为费用
+    SetCard {
+        player: PlayerId,
+        card: CardId,
+        zone: ZoneKey,
+        cost_hand: Vec<CardId>,
+        cost_jq: Vec<CardId>,
+    },
+    // 战场上的人物攻击同一列对方战场
+    DeclareAttack {
+        player: PlayerId,
+        attacker: CardId,
+    },
+    // 发动神秘术 可以指定一张卡作为目标
+    CastArcane {
+        player: PlayerId,
+        card: CardId,
+        target: Option<CardId>,
+        cost_hand: Vec<CardId>,
+        cost_jq: Vec<CardId>,
+    },
+    // 卡片效果 source 是发动效果的卡
+    Effect {
+        source: CardId,
+        effect: Effect,
+    },
+}
+
+// 规则产生的事件
+#[derive(Clone, Debug, PartialEq)]
+pub enum RuleEvent {
+    CardMoved {
+        card: CardId,
+        from: Location,
+        to: Location,
+    },
+    // 在原位置竖直
+    CardStood {
+        card: CardId,
+    },
+    PhaseStarted {
+        player: PlayerId,
+        turn: u32,
+        phase: Phase,
+    },
+    PhaseEnded {
+        player: PlayerId,
+        turn: u32,
+        phase: Phase,
+    },
+    // 模因附着到人物上
+    CardAttached {
+        player: PlayerId,
+        card: CardId,
+        host: CardId,
+    },
+    // 宣言攻击 对方同一列没有人物时为空
+    AttackDeclared {
+        player: PlayerId,
+        attacker: CardId,
+        defender: Option<CardId>,
+    },
+    // 战斗结算 攻击力相同时没有胜者
+    BattleResolved {
+        attacker: CardId,
+        defender: CardId,
+        winner: Option<CardId>,
+    },
+    // 玩家受到伤害
+    PlayerDamaged {
+        player: PlayerId,
+        amount: u32,
+        life: u32,
+    },
+    GameOver {
+        winner: Option<PlayerId>,
+        reason: GameOverReason,
+    },
+    // 被破坏 随后送去墓地
+    CardDestroyed {
+        player: PlayerId,
+        card: CardId,
+    },
+    // 神秘术发动 和送去墓地在同一个操作中 脚本的效果在这之后结算
+    ArcaneCast {
+        player: PlayerId,
+        card: CardId,
+        target: Option<CardId>,
+    },
+    // 卡片登场完成
+    CardEntered {
+        player: PlayerId,
+        card: CardId,
+        zone: ZoneKey,
+    },
+    // 卡组洗切后的顺序 最后一张在最上面
+    DeskShuffled {
+        player: PlayerId,
+        order: Vec<CardId>,
+    },
+    // 攻击力变化后的数值
+    AckModified {
+        card: CardId,
+        ack: u32,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RuleError {
+    UnknownPlayer(PlayerId),
+    UnknownCard(CardId),
+    // 卡组没有卡了
+    EmptyDesk(PlayerId),
+    ZoneFull(PlayerId, ZoneKey),
+    // 棋盘上没有这个区域
+    UnknownZone(PlayerId, ZoneKey),
+    NotInHand(CardId),
+    MatchNotStarted,
+    MatchAlreadyStarted,
+    // 不是当前回合的玩家
+    NotActivePlayer(PlayerId),
+    // 当前阶段不能进行这个操作
+    WrongPhase(Phase),
+    // 卡片信息还没有加载
+    InfoNotLoaded(CardId),
+    CannotSetOn(CardId, ZoneKey),
+    NotInZone(CardId, ZoneKey),
+    NotOnField(CardId),
+    CannotMoveTo(CardId, Location),
+    // 支付的费用和需要的不一致
+    CostMismatch { need: usize, paid: usize },
+    // 同一张卡被重复使用
+    DuplicateCard(CardId),
+    NotArcane(CardId),
+    // 不在战场上的人物不能攻击
+    CannotAttack(CardId),
+    AlreadyAttacked(CardId),
+    // 对局已经结束
+    GameOver,
+}
+
+impl std::fmt::Display for RuleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuleError::UnknownPlayer(player) => write!(f, "unknown player {:?}", player),
+            RuleError::UnknownCard(card) => write!(f, "unknown card {}", card),
+            RuleError::EmptyDesk(player) => write!(f, "desk of {:?} is empty", player),
+            RuleError::ZoneFull(player, zone) => write!(f, "{:?} of {:?} is full", zone, player),
+            RuleError::UnknownZone(player, zone) => {
+                write!(f, "{:?} of {:?} is not on the board", zone, player)
+            }
+            RuleError::NotInHand(card) => write!(f, "card {} is not in hand", card),
+            RuleError::MatchNotStarted => write!(f, "match is not started"),
+            RuleError::MatchAlreadyStarted => write!(f, "match is already started"),
+            RuleError::NotActivePlayer(player) => {
+                write!(f, "{:?} is not the active player", player)
+            }
+            RuleError::WrongPhase(phase) => write!(f, "not allowed in {:?} phase", phase),
+            RuleError::InfoNotLoaded(card) => write!(f, "card {} info is not loaded", card),
+            RuleError::CannotSetOn(card, zone) => {
+                write!(f, "card {} cannot set on {:?}", card, zone)
+            }
+            RuleError::NotInZone(card, zone) => write!(f, "card {} is not in {:?}", card, zone),
+            RuleError::NotOnField(card) => write!(f, "card {} is not on the field", card),
+            RuleError::CannotMoveTo(card, to) => write!(f, "card {} cannot move to {:?}", card, to),
+            RuleError::CostMismatch { need, paid } => {
+                write!(f, "cost mismatch need {} paid {}", need, paid)
+            }
+            RuleError::DuplicateCard(card) => write!(f, "card {} is used twice", card),
+            RuleError::NotArcane(card) => write!(f, "card {} is not an arcane", card),
+            RuleError::CannotAttack(card) => write!(f, "card {} cannot attack", card),
+            RuleError::AlreadyAttacked(card) => write!(f, "card {} already attacked", card),
+            RuleError::GameOver => write!(f, "match is over"),
+        }
+    }
+}
+
+impl std::error::Error for RuleError {}
@@ -0,0 +1,52 @@
+/// 对局的随机数
+/// 同样的种子和同样的操作一定得到同样的结果 不依赖外部的随机数库
+use serde::{Deserialize, Serialize};
+
+// SplitMix64
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatchRng {
+    state: u64,
+}
+
+impl MatchRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    // 当前状态 保存对局时使用
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // 0..n 中的一个数 n 为 0 时返回 0
+    pub fn below(&mut self, n: usize) -> usize {
+        if n == 0 {
+            return 0;
+        }
+        // 拒绝采样 避免取模带来的偏差
+        let n = n as u64;
+        let zone = u64::MAX - u64::MAX % n;
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return (value % n) as usize;
+            }
+        }
+    }
+
+    // Fisher-Yates 洗牌
+    pub fn shuffle<T>(&mut self, list: &mut [T]) {
+        for i in (1..list.len()).rev() {
+            let j = self.below(i + 1);
+            list.swap(i, j);
+        }
+    }
+}
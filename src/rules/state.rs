@@ -0,0 +1,1940 @@
+use crate::card_info::CardInfo;
+use crate::card_info::card_enums::{CardType, Placement};
+use crate::rules::cost::CostSolver;
+use crate::rules::rng::MatchRng;
+use crate::rules::{
+    AckModifier, Action, CardId, CardInstance, CardPose, Effect, EffectDuration, GameOverReason,
+    Location, Phase, PlayerId, RuleError, RuleEvent, STARTING_LIFE, ZoneKey, ZoneRule,
+};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+// 单个玩家的状态
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerState {
+    pub id: PlayerId,
+    pub life: u32,
+    pub hand: Vec<CardId>,
+    pub zones: HashMap<ZoneKey, Vec<CardId>>,
+    // 人物身上的装备
+    pub attached: HashMap<CardId, Vec<CardId>>,
+    // 没有规则的区域不限容量 不能登场
+    #[serde(default)]
+    pub rules: HashMap<ZoneKey, ZoneRule>,
+}
+
+impl PlayerState {
+    pub fn new(id: PlayerId) -> Self {
+        Self::with_zones(id, &ZoneKey::ALL)
+    }
+
+    // 使用指定的区域 战场和安全屋的数量可以不同
+    pub fn with_zones(id: PlayerId, keys: &[ZoneKey]) -> Self {
+        Self {
+            id,
+            life: STARTING_LIFE,
+            hand: vec![],
+            zones: keys.iter().map(|key| (*key, vec![])).collect(),
+            attached: HashMap::new(),
+            rules: HashMap::new(),
+        }
+    }
+
+    // 某一类区域的所有编号 从小到大
+    pub fn slots(&self, kind: fn(usize) -> ZoneKey) -> Vec<usize> {
+        let mut slots: Vec<usize> = self
+            .zones
+            .keys()
+            .filter_map(|key| key.slot().filter(|slot| kind(*slot) == *key))
+            .collect();
+        slots.sort();
+        slots
+    }
+
+    pub fn zone(&self, key: ZoneKey) -> &[CardId] {
+        self.zones
+            .get(&key)
+            .map(|list| list.as_slice())
+            .unwrap_or(&[])
+    }
+
+    // 区域最上面的卡 洞悉时下面会叠放原来的卡
+    pub fn top(&self, key: ZoneKey) -> Option<CardId> {
+        self.zone(key).last().copied()
+    }
+
+    pub fn attachments(&self, host: CardId) -> &[CardId] {
+        self.attached
+            .get(&host)
+            .map(|list| list.as_slice())
+            .unwrap_or(&[])
+    }
+
+    // 区域剩余空位 不限容量的区域返回 usize::MAX
+    pub fn zone_free(&self, key: ZoneKey) -> usize {
+        match self.rules.get(&key).and_then(|rule| rule.capacity) {
+            Some(capacity) => capacity.saturating_sub(self.zone(key).len()),
+            None => usize::MAX,
+        }
+    }
+
+    // 可以登场的卡片类型
+    pub fn accepts(&self, key: ZoneKey, card_type: &CardType) -> bool {
+        self.rules
+            .get(&key)
+            .is_some_and(|rule| rule.accepts.contains(card_type))
+    }
+}
+
+// 整个对局的状态 可以保存到文件
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameState {
+    pub players: Vec<PlayerState>,
+    pub cards: HashMap<CardId, CardInstance>,
+    // 当前回合的玩家
+    pub active_player: PlayerId,
+    // 回合数 从 1 开始
+    pub turn: u32,
+    // 对局开始前为空
+    pub phase: Option<Phase>,
+    // 本回合已经攻击过的人物
+    pub attacked: Vec<CardId>,
+    // 对局结果 胜者为空时是平局
+    pub game_over: Option<(Option<PlayerId>, GameOverReason)>,
+    // 对局的随机数种子 洗牌和随机效果都使用 rng
+    pub seed: u64,
+    pub rng: MatchRng,
+    // 效果带来的攻击力变化
+    #[serde(default)]
+    pub modifiers: Vec<AckModifier>,
+    next_card_id: CardId,
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GameState {
+    pub fn new() -> Self {
+        Self::with_seed(0)
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            players: vec![
+                PlayerState::new(PlayerId::MY),
+                PlayerState::new(PlayerId::OPPONENT),
+            ],
+            cards: HashMap::new(),
+            active_player: PlayerId::MY,
+            turn: 0,
+            phase: None,
+            attacked: vec![],
+            game_over: None,
+            seed,
+            rng: MatchRng::new(seed),
+            modifiers: vec![],
+            next_card_id: 1,
+        }
+    }
+
+    pub fn player(&self, player: PlayerId) -> Result<&PlayerState, RuleError> {
+        self.players
+            .iter()
+            .find(|p| p.id == player)
+            .ok_or(RuleError::UnknownPlayer(player))
+    }
+
+    fn player_mut(&mut self, player: PlayerId) -> Result<&mut PlayerState, RuleError> {
+        self.players
+            .iter_mut()
+            .find(|p| p.id == player)
+            .ok_or(RuleError::UnknownPlayer(player))
+    }
+
+    // 按棋盘重新设置玩家的区域和区域的规则 只能在对局开始前进行
+    pub fn set_board(
+        &mut self,
+        player: PlayerId,
+        board: &[(ZoneKey, ZoneRule)],
+    ) -> Result<(), RuleError> {
+        if self.phase.is_some() {
+            return Err(RuleError::MatchAlreadyStarted);
+        }
+        let player_state = self.player_mut(player)?;
+        // 已经有卡的区域保留
+        player_state.zones.retain(|key, list| {
+            board.iter().any(|(board_key, _)| board_key == key) || !list.is_empty()
+        });
+        for (key, _) in board {
+            player_state.zones.entry(*key).or_default();
+        }
+        player_state.rules = board.iter().cloned().collect();
+        Ok(())
+    }
+
+    // 重新设置随机数种子 只能在对局开始前进行
+    pub fn reseed(&mut self, seed: u64) -> Result<(), RuleError> {
+        if self.phase.is_some() {
+            return Err(RuleError::MatchAlreadyStarted);
+        }
+        self.seed = seed;
+        self.rng = MatchRng::new(seed);
+        Ok(())
+    }
+
+    // 0..n 中的一个随机数
+    pub fn random(&mut self, n: usize) -> usize {
+        self.rng.below(n)
+    }
+
+    pub fn card(&self, card: CardId) -> Result<&CardInstance, RuleError> {
+        self.cards.get(&card).ok_or(RuleError::UnknownCard(card))
+    }
+
+    pub fn card_info(&self, card: CardId) -> Option<&CardInfo> {
+        self.cards.get(&card).and_then(|c| c.info.as_ref())
+    }
+
+    // 在某个位置放入一张新卡
+    pub fn add_card(
+        &mut self,
+        owner: PlayerId,
+        info: Option<CardInfo>,
+        location: Location,
+    ) -> Result<CardId, RuleError> {
+        let id = self.next_card_id;
+        let pose = match location {
+            Location::Zone(_, key) => key.default_pose(),
+            _ => None,
+        }
+        .unwrap_or(CardPose {
+            face_up: true,
+            vertical: true,
+        });
+        self.list_mut(location)?.push(id);
+        self.cards.insert(
+            id,
+            CardInstance {
+                id,
+                owner,
+                info,
+                location,
+                pose,
+            },
+        );
+        self.next_card_id += 1;
+        Ok(id)
+    }
+
+    // 脚本加载完成之后补充卡片信息
+    pub fn set_card_info(&mut self, card: CardId, info: CardInfo) -> Result<(), RuleError> {
+        self.cards
+            .get_mut(&card)
+            .ok_or(RuleError::UnknownCard(card))?
+            .info = Some(info);
+        Ok(())
+    }
+
+    pub fn list(&self, location: Location) -> Result<&[CardId], RuleError> {
+        let player = self.player(location.player())?;
+        Ok(match location {
+            Location::Hand(_) => player.hand.as_slice(),
+            Location::Zone(_, key) => player.zone(key),
+            Location::Attached(_, host) => player.attachments(host),
+        })
+    }
+
+    fn list_mut(&mut self, location: Location) -> Result<&mut Vec<CardId>, RuleError> {
+        let player = self.player_mut(location.player())?;
+        Ok(match location {
+            Location::Hand(_) => &mut player.hand,
+            Location::Zone(player_id, key) => player
+                .zones
+                .get_mut(&key)
+                .ok_or(RuleError::UnknownZone(player_id, key))?,
+            Location::Attached(_, host) => player.attached.entry(host).or_default(),
+        })
+    }
+
+    pub fn is_actor(&self, card: CardId) -> bool {
+        matches!(self.card_info(card), Some(info) if info.card_type == CardType::Actor)
+    }
+
+    // 人物当前的攻击力 加上装备的模因和效果的增减
+    pub fn effective_ack(&self, card: CardId) -> u32 {
+        let Ok(instance) = self.card(card) else {
+            return 0;
+        };
+        let base = instance.info.as_ref().map(|info| info.ack).unwrap_or(0);
+        let bonus: u32 = self
+            .player(instance.owner)
+            .map(|player| {
+                player
+                    .attachments(card)
+                    .iter()
+                    .filter_map(|meme| self.card_info(*meme))
+                    .map(|info| info.ack)
+                    .sum()
+            })
+            .unwrap_or(0);
+        let delta: i64 = self
+            .modifiers
+            .iter()
+            .filter(|m| m.card == card)
+            .map(|m| m.delta as i64)
+            .sum();
+        (base as i64 + bonus as i64 + delta).max(0) as u32
+    }
+
+    // 位置所在的区域必须在棋盘上
+    fn check_location(&self, location: Location) -> Result<(), RuleError> {
+        let player_state = self.player(location.player())?;
+        match location {
+            Location::Zone(player, key) if !player_state.zones.contains_key(&key) => {
+                Err(RuleError::UnknownZone(player, key))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // 移动卡片 调用前需要检查容量 区域不存在时不做修改
+    // 离开场上时 装备的模因一起送去墓地
+    fn move_card(&mut self, card: CardId, to: Location) -> Result<Vec<RuleEvent>, RuleError> {
+        let from = self.card(card)?.location;
+        let left_field = match (from, to) {
+            (Location::Zone(_, from_key), Location::Zone(_, to_key)) => {
+                from_key.is_field() && !to_key.is_field()
+            }
+            (Location::Zone(_, from_key), _) => from_key.is_field(),
+            _ => false,
+        };
+        self.check_location(to)?;
+        if left_field {
+            for meme in self.player(from.player())?.attachments(card) {
+                let meme_owner = self.card(*meme)?.owner;
+                self.check_location(Location::Zone(meme_owner, ZoneKey::Grave))?;
+            }
+        }
+        self.list_mut(from)?.retain(|c| *c != card);
+        self.list_mut(to)?.push(card);
+        let instance = self
+            .cards
+            .get_mut(&card)
+            .ok_or(RuleError::UnknownCard(card))?;
+        instance.location = to;
+        match to {
+            Location::Zone(_, key) => {
+                if let Some(pose) = key.default_pose() {
+                    instance.pose = pose;
+                }
+            }
+            Location::Hand(_) | Location::Attached(..) => {
+                instance.pose = CardPose {
+                    face_up: true,
+                    vertical: true,
+                };
+            }
+        }
+        let mut events = vec![RuleEvent::CardMoved { card, from, to }];
+        if left_field {
+            // 离场后效果带来的变化失效
+            self.modifiers.retain(|m| m.card != card);
+            let owner = from.player();
+            let memes = self
+                .player_mut(owner)?
+                .attached
+                .remove(&card)
+                .unwrap_or_default();
+            for meme in memes {
+                // 已经从列表中取出 直接放入墓地
+                let meme_owner = self.card(meme)?.owner;
+                self.list_mut(Location::Zone(meme_owner, ZoneKey::Grave))?
+                    .push(meme);
+                let to = Location::Zone(meme_owner, ZoneKey::Grave);
+                if let Some(instance) = self.cards.get_mut(&meme) {
+                    instance.location = to;
+                }
+                events.push(RuleEvent::CardMoved {
+                    card: meme,
+                    from: Location::Attached(owner, card),
+                    to,
+                });
+            }
+        }
+        Ok(events)
+    }
+
+    // 棋盘上没有的区域不能放入
+    fn check_free(&self, player: PlayerId, key: ZoneKey, need: usize) -> Result<(), RuleError> {
+        self.check_location(Location::Zone(player, key))?;
+        if self.player(player)?.zone_free(key) < need {
+            return Err(RuleError::ZoneFull(player, key));
+        }
+        Ok(())
+    }
+
+    // 执行一个操作 失败时状态不变
+    pub fn apply(&mut self, action: Action) -> Result<Vec<RuleEvent>, RuleError> {
+        if self.game_over.is_some() {
+            return Err(RuleError::GameOver);
+        }
+        match action {
+            Action::Draw { player } => {
+                self.check_phase(player, Phase::Draw)?;
+                self.draw(player)
+            }
+            Action::PutHandInLx { player, card } => {
+                self.check_phase(player, Phase::Main)?;
+                if !self.player(player)?.hand.contains(&card) {
+                    return Err(RuleError::NotInHand(card));
+                }
+                self.check_free(player, ZoneKey::Lx, 1)?;
+                self.move_card(card, Location::Zone(player, ZoneKey::Lx))
+            }
+            Action::PutDeskInJq { player } => {
+                self.check_phase(player, Phase::Main)?;
+                self.check_free(player, ZoneKey::Jq, 1)?;
+                let card = *self
+                    .player(player)?
+                    .zone(ZoneKey::Desk)
+                    .last()
+                    .ok_or(RuleError::EmptyDesk(player))?;
+                self.move_card(card, Location::Zone(player, ZoneKey::Jq))
+            }
+            Action::RecoverLx { player } => {
+                self.check_phase(player, Phase::Recovery)?;
+                let mut events = vec![];
+                for card in self.player(player)?.zone(ZoneKey::Lx).to_vec() {
+                    if self.card(card)?.pose.vertical {
+                        events.extend(self.move_card(card, Location::Hand(player))?);
+                    } else {
+                        if let Some(instance) = self.cards.get_mut(&card) {
+                            instance.pose.vertical = true;
+                        }
+                        events.push(RuleEvent::CardStood { card });
+                    }
+                }
+                Ok(events)
+            }
+            Action::Shuffle { player } => Ok(vec![self.shuffle(player)?]),
+            Action::StartMatch { first } => {
+                if self.phase.is_some() {
+                    return Err(RuleError::MatchAlreadyStarted);
+                }
+                self.player(first)?;
+                let mut events = vec![];
+                for player in self.players.iter().map(|p| p.id).collect::<Vec<_>>() {
+                    events.push(self.shuffle(player)?);
+                }
+                self.active_player = first;
+                self.turn = 1;
+                self.phase = Some(Phase::Draw);
+                events.push(RuleEvent::PhaseStarted {
+                    player: first,
+                    turn: self.turn,
+                    phase: Phase::Draw,
+                });
+                Ok(events)
+            }
+            Action::AdvancePhase { player } => {
+                let phase = self.phase.ok_or(RuleError::MatchNotStarted)?;
+                if player != self.active_player {
+                    return Err(RuleError::NotActivePlayer(player));
+                }
+                let mut events = vec![RuleEvent::PhaseEnded {
+                    player,
+                    turn: self.turn,
+                    phase,
+                }];
+                let next = phase.next();
+                if phase == Phase::End {
+                    events.extend(self.expire_modifiers());
+                    // 交换回合
+                    self.active_player = player.other();
+                    self.turn += 1;
+                    self.attacked.clear();
+                }
+                self.phase = Some(next);
+                events.push(RuleEvent::PhaseStarted {
+                    player: self.active_player,
+                    turn: self.turn,
+                    phase: next,
+                });
+                Ok(events)
+            }
+            Action::SetCard {
+                player,
+                card,
+                zone,
+                cost_hand,
+                cost_jq,
+            } => self.set_card(player, card, zone, cost_hand, cost_jq),
+            Action::DeclareAttack { player, attacker } => self.declare_attack(player, attacker),
+            Action::CastArcane {
+                player,
+                card,
+                target,
+                cost_hand,
+                cost_jq,
+            } => self.cast_arcane(player, card, target, cost_hand, cost_jq),
+            Action::Effect { source, effect } => {
+                self.card(source)?;
+                self.apply_effect(effect)
+            }
+        }
+    }
+
+    // 结算卡片效果 检查都在修改状态之前
+    fn apply_effect(&mut self, effect: Effect) -> Result<Vec<RuleEvent>, RuleError> {
+        match effect {
+            Effect::Draw { player, count } => {
+                // 对局开始前卡组不够时不抽 对局中抽完判负
+                if self.phase.is_none() && self.player(player)?.zone(ZoneKey::Desk).len() < count {
+                    return Err(RuleError::EmptyDesk(player));
+                }
+                let mut events = vec![];
+                for _ in 0..count {
+                    events.extend(self.draw(player)?);
+                    if self.game_over.is_some() {
+                        break;
+                    }
+                }
+                Ok(events)
+            }
+            Effect::MoveCard { card, to } => {
+                let instance = self.card(card)?;
+                // 附着只能通过登场
+                if let Location::Attached(..) = to {
+                    return Err(RuleError::CannotMoveTo(card, to));
+                }
+                if instance.location == to {
+                    return Ok(vec![]);
+                }
+                self.list(to)?;
+                if let Location::Zone(player, key) = to {
+                    self.check_free(player, key, 1)?;
+                    let accepts = instance.info.as_ref().is_some_and(|info| {
+                        self.player(player)
+                            .is_ok_and(|player_state| player_state.accepts(key, &info.card_type))
+                    });
+                    if key.is_field() && !accepts {
+                        return Err(RuleError::CannotSetOn(card, key));
+                    }
+                }
+                self.move_card(card, to)
+            }
+            Effect::Destroy { card } => {
+                if !self.card(card)?.location.on_field() {
+                    return Err(RuleError::NotOnField(card));
+                }
+                self.destroy(card)
+            }
+            Effect::ModifyAck {
+                card,
+                delta,
+                duration,
+            } => {
+                if !self.card(card)?.location.on_field() {
+                    return Err(RuleError::NotOnField(card));
+                }
+                let until_turn = match duration {
+                    EffectDuration::EndOfTurn => Some(self.turn),
+                    EffectDuration::Turns(turns) => {
+                        Some(self.turn.saturating_add(turns.max(1) - 1))
+                    }
+                    EffectDuration::Permanent => None,
+                };
+                self.modifiers.push(AckModifier {
+                    card,
+                    delta,
+                    until_turn,
+                });
+                Ok(vec![RuleEvent::AckModified {
+                    card,
+                    ack: self.effective_ack(card),
+                }])
+            }
+            Effect::Discard { player, count } => {
+                let mut events = vec![];
+                for _ in 0..count {
+                    let hand = self.player(player)?.hand.clone();
+                    if hand.is_empty() {
+                        break;
+                    }
+                    let card = hand[self.rng.below(hand.len())];
+                    events.extend(self.move_card(card, Location::Zone(player, ZoneKey::Grave))?);
+                }
+                Ok(events)
+            }
+            Effect::SearchDeck { player, card } => {
+                if self.card(card)?.location != Location::Zone(player, ZoneKey::Desk) {
+                    return Err(RuleError::NotInZone(card, ZoneKey::Desk));
+                }
+                let mut events = self.move_card(card, Location::Hand(player))?;
+                events.push(self.shuffle(player)?);
+                Ok(events)
+            }
+        }
+    }
+
+    // 回合结束时失效的攻击力变化
+    fn expire_modifiers(&mut self) -> Vec<RuleEvent> {
+        let turn = self.turn;
+        let mut expired: Vec<CardId> = self
+            .modifiers
+            .iter()
+            .filter(|m| m.until_turn.is_some_and(|until| until <= turn))
+            .map(|m| m.card)
+            .collect();
+        self.modifiers
+            .retain(|m| m.until_turn.is_none_or(|until| until > turn));
+        expired.sort();
+        expired.dedup();
+        expired
+            .into_iter()
+            .map(|card| RuleEvent::AckModified {
+                card,
+                ack: self.effective_ack(card),
+            })
+            .collect()
+    }
+
+    // 抽一张 对局中卡组抽完判负
+    fn draw(&mut self, player: PlayerId) -> Result<Vec<RuleEvent>, RuleError> {
+        let Some(card) = self.player(player)?.top(ZoneKey::Desk) else {
+            if self.phase.is_some() {
+                return Ok(vec![
+                    self.finish(Some(player.other()), GameOverReason::DeckOut),
+                ]);
+            }
+            return Err(RuleError::EmptyDesk(player));
+        };
+        self.move_card(card, Location::Hand(player))
+    }
+
+    // 对局开始后只有当前玩家的对应阶段可以操作
+    fn check_phase(&self, player: PlayerId, need: Phase) -> Result<(), RuleError> {
+        match self.phase {
+            None => Ok(()),
+            Some(_) if player != self.active_player => Err(RuleError::NotActivePlayer(player)),
+            Some(phase) if phase == need => Ok(()),
+            Some(phase) => Err(RuleError::WrongPhase(phase)),
+        }
+    }
+
+    // 可以从手卡或者理性区打出
+    fn check_playable(&self, player: PlayerId, card: CardId) -> Result<Location, RuleError> {
+        let from = self.card(card)?.location;
+        if from != Location::Hand(player) && from != Location::Zone(player, ZoneKey::Lx) {
+            return Err(RuleError::NotInHand(card));
+        }
+        Ok(from)
+    }
+
+    // 检查费用的支付方式
+    fn check_cost(
+        &self,
+        player: PlayerId,
+        card: CardId,
+        cost: usize,
+        cost_hand: &[CardId],
+        cost_jq: &[CardId],
+    ) -> Result<(), RuleError> {
+        let from = self.card(card)?.location;
+        let mut used = vec![card];
+        for cost_card in cost_hand.iter().chain(cost_jq.iter()) {
+            if used.contains(cost_card) {
+                return Err(RuleError::DuplicateCard(*cost_card));
+            }
+            used.push(*cost_card);
+        }
+        let player_state = self.player(player)?;
+        for cost_card in cost_hand.iter() {
+            if !player_state.hand.contains(cost_card) {
+                return Err(RuleError::NotInHand(*cost_card));
+            }
+        }
+        for cost_card in cost_jq.iter() {
+            if !player_state.zone(ZoneKey::Jq).contains(cost_card) {
+                return Err(RuleError::NotInZone(*cost_card, ZoneKey::Jq));
+            }
+        }
+        // 从理性区打出时会空出一格
+        let lx_free = player_state.zone_free(ZoneKey::Lx)
+            + usize::from(from == Location::Zone(player, ZoneKey::Lx));
+        let hand = player_state.hand.len() - usize::from(from == Location::Hand(player));
+        let solution =
+            CostSolver::new(cost, hand, lx_free, player_state.zone(ZoneKey::Jq).len()).solve();
+        if !solution.accepts(cost_hand.len(), cost_jq.len()) {
+            return Err(RuleError::CostMismatch {
+                need: solution.cost,
+                paid: cost_hand.len() + cost_jq.len(),
+            });
+        }
+        Ok(())
+    }
+
+    // 支付费用 手卡放入理性区 激情送去墓地
+    fn pay_cost(
+        &mut self,
+        player: PlayerId,
+        cost_hand: Vec<CardId>,
+        cost_jq: Vec<CardId>,
+    ) -> Result<Vec<RuleEvent>, RuleError> {
+        let mut events = vec![];
+        for cost_card in cost_hand {
+            events.extend(self.move_card(cost_card, Location::Zone(player, ZoneKey::Lx))?);
+        }
+        for cost_card in cost_jq {
+            events.extend(self.move_card(cost_card, Location::Zone(player, ZoneKey::Grave))?);
+        }
+        Ok(events)
+    }
+
+    fn set_card(
+        &mut self,
+        player: PlayerId,
+        card: CardId,
+        zone: ZoneKey,
+        cost_hand: Vec<CardId>,
+        cost_jq: Vec<CardId>,
+    ) -> Result<Vec<RuleEvent>, RuleError> {
+        self.check_phase(player, Phase::Main)?;
+        let info = self.card_info(card).ok_or(RuleError::InfoNotLoaded(card))?;
+        let cost = info.cost;
+        let placement = info.placement;
+        self.check_playable(player, card)?;
+        if !self.player(player)?.accepts(zone, &info.card_type) {
+            return Err(RuleError::CannotSetOn(card, zone));
+        }
+        let replaced = self.player(player)?.zone(zone).to_vec();
+        // 模因放在人物所在的位置时 作为装备附着在最上面的人物上
+        let host = if info.card_type == CardType::Meme
+            && matches!(zone, ZoneKey::Battle(_) | ZoneKey::Prepare)
+        {
+            let host = self
+                .player(player)?
+                .top(zone)
+                .filter(|c| self.is_actor(*c))
+                .ok_or(RuleError::CannotSetOn(card, zone))?;
+            Some(host)
+        } else {
+            // 牺牲和洞悉可以放在已有的人物上
+            if replaced.is_empty() || placement == Placement::Normal {
+                self.check_free(player, zone, 1)?;
+            } else if !replaced.iter().any(|c| self.is_actor(*c)) {
+                return Err(RuleError::CannotSetOn(card, zone));
+            }
+            None
+        };
+        self.check_cost(player, card, cost, &cost_hand, &cost_jq)?;
+
+        let mut events = vec![];
+        if let Some(host) = host {
+            events.extend(self.move_card(card, Location::Attached(player, host))?);
+            events.push(RuleEvent::CardAttached { player, card, host });
+        } else {
+            if placement == Placement::Sacrifice {
+                for replaced_card in replaced {
+                    events.extend(
+                        self.move_card(replaced_card, Location::Zone(player, ZoneKey::Grave))?,
+                    );
+                }
+            }
+            events.extend(self.move_card(card, Location::Zone(player, zone))?);
+        }
+        events.extend(self.pay_cost(player, cost_hand, cost_jq)?);
+        events.push(RuleEvent::CardEntered { player, card, zone });
+        Ok(events)
+    }
+
+    // 发动神秘术 先送去墓地 效果由 ArcaneCast 触发的脚本随后结算
+    fn cast_arcane(
+        &mut self,
+        player: PlayerId,
+        card: CardId,
+        target: Option<CardId>,
+        cost_hand: Vec<CardId>,
+        cost_jq: Vec<CardId>,
+    ) -> Result<Vec<RuleEvent>, RuleError> {
+        self.check_phase(player, Phase::Main)?;
+        let info = self.card_info(card).ok_or(RuleError::InfoNotLoaded(card))?;
+        if info.card_type != CardType::Arcane {
+            return Err(RuleError::NotArcane(card));
+        }
+        let cost = info.cost;
+        self.check_playable(player, card)?;
+        // 只能以场上的卡为目标
+        if let Some(target) = target
+            && !self.card(target)?.location.on_field()
+        {
+            return Err(RuleError::NotOnField(target));
+        }
+        self.check_cost(player, card, cost, &cost_hand, &cost_jq)?;
+
+        let mut events = vec![RuleEvent::ArcaneCast {
+            player,
+            card,
+            target,
+        }];
+        events.extend(self.move_card(card, Location::Zone(player, ZoneKey::Grave))?);
+        events.extend(self.pay_cost(player, cost_hand, cost_jq)?);
+        Ok(events)
+    }
+
+    fn shuffle(&mut self, player: PlayerId) -> Result<RuleEvent, RuleError> {
+        let mut order = self.player(player)?.zone(ZoneKey::Desk).to_vec();
+        self.rng.shuffle(&mut order);
+        *self.list_mut(Location::Zone(player, ZoneKey::Desk))? = order.clone();
+        Ok(RuleEvent::DeskShuffled { player, order })
+    }
+
+    // 人物所在的战场
+    fn battle_slot(&self, card: CardId) -> Option<usize> {
+        match self.cards.get(&card)?.location {
+            Location::Zone(player, ZoneKey::Battle(slot)) => {
+                (self.player(player).ok()?.top(ZoneKey::Battle(slot)) == Some(card)).then_some(slot)
+            }
+            _ => None,
+        }
+    }
+
+    // 破坏 送去持有者的墓地
+    fn destroy(&mut self, card: CardId) -> Result<Vec<RuleEvent>, RuleError> {
+        let owner = self.card(card)?.owner;
+        let mut events = vec![RuleEvent::CardDestroyed {
+            player: owner,
+            card,
+        }];
+        events.extend(self.move_card(card, Location::Zone(owner, ZoneKey::Grave))?);
+        Ok(events)
+    }
+
+    // 攻击同一列对方战场上的人物 比较攻击力 输的一方被破坏 相同时都被破坏
+    fn declare_attack(
+        &mut self,
+        player: PlayerId,
+        attacker: CardId,
+    ) -> Result<Vec<RuleEvent>, RuleError> {
+        self.check_phase(player, Phase::Battle)?;
+        if self.card(attacker)?.owner != player || !self.is_actor(attacker) {
+            return Err(RuleError::CannotAttack(attacker));
+        }
+        let slot = self
+            .battle_slot(attacker)
+            .ok_or(RuleError::CannotAttack(attacker))?;
+        if self.attacked.contains(&attacker) {
+            return Err(RuleError::AlreadyAttacked(attacker));
+        }
+        let defender = self
+            .player(player.other())?
+            .top(ZoneKey::Battle(slot))
+            .filter(|c| self.is_actor(*c));
+        self.attacked.push(attacker);
+
+        let mut events = vec![RuleEvent::AttackDeclared {
+            player,
+            attacker,
+            defender,
+        }];
+        let Some(defender) = defender else {
+            // 没有阻挡 对方受到攻击力的伤害
+            events.extend(self.damage(player.other(), self.effective_ack(attacker))?);
+            return Ok(events);
+        };
+        let attacker_ack = self.effective_ack(attacker);
+        let defender_ack = self.effective_ack(defender);
+        let winner = match attacker_ack.cmp(&defender_ack) {
+            Ordering::Greater => Some(attacker),
+            Ordering::Less => Some(defender),
+            Ordering::Equal => None,
+        };
+        events.push(RuleEvent::BattleResolved {
+            attacker,
+            defender,
+            winner,
+        });
+        if winner != Some(attacker) {
+            events.extend(self.destroy(attacker)?);
+        }
+        if winner != Some(defender) {
+            events.extend(self.destroy(defender)?);
+        }
+        Ok(events)
+    }
+
+    // 玩家受到伤害 生命为 0 时判负
+    fn damage(&mut self, player: PlayerId, amount: u32) -> Result<Vec<RuleEvent>, RuleError> {
+        let player_state = self.player_mut(player)?;
+        player_state.life = player_state.life.saturating_sub(amount);
+        let life = player_state.life;
+        let mut events = vec![RuleEvent::PlayerDamaged {
+            player,
+            amount,
+            life,
+        }];
+        if life == 0 {
+            events.push(self.finish(Some(player.other()), GameOverReason::LifeZero));
+        }
+        Ok(events)
+    }
+
+    // 对局结束
+    fn finish(&mut self, winner: Option<PlayerId>, reason: GameOverReason) -> RuleEvent {
+        self.game_over = Some((winner, reason));
+        RuleEvent::GameOver { winner, reason }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card_info::card_enums::{Attr, Race};
+
+    fn info(card_type: CardType, cost: usize) -> Option<CardInfo> {
+        Some(CardInfo {
+            id: "TEST".to_string(),
+            name: "test".to_string(),
+            card_type,
+            attr: Attr::STAR,
+            race: Race::NULL,
+            cost,
+            ack: 1000,
+            placement: Placement::Normal,
+        })
+    }
+
+    // 和默认棋盘布局相同 理性区和激情区只有两格 方便测试放满
+    fn board() -> Vec<(ZoneKey, ZoneRule)> {
+        let rule = |capacity, accepts: &[CardType]| ZoneRule {
+            capacity,
+            accepts: accepts.to_vec(),
+        };
+        vec![
+            (ZoneKey::Desk, rule(None, &[])),
+            (ZoneKey::Grave, rule(None, &[])),
+            (ZoneKey::Lx, rule(Some(2), &[])),
+            (ZoneKey::Jq, rule(Some(2), &[])),
+            (
+                ZoneKey::Prepare,
+                rule(Some(1), &[CardType::Actor, CardType::Meme]),
+            ),
+            (
+                ZoneKey::Battle(1),
+                rule(Some(1), &[CardType::Actor, CardType::Meme]),
+            ),
+            (ZoneKey::Safe(1), rule(Some(1), &[CardType::Meme])),
+        ]
+    }
+
+    // 双方卡组各有 desk 张人物
+    fn game(desk: usize) -> GameState {
+        let mut state = GameState::with_seed(7);
+        for player in PlayerId::ALL {
+            state.set_board(player, &board()).unwrap();
+            for _ in 0..desk {
+                state
+                    .add_card(
+                        player,
+                        info(CardType::Actor, 0),
+                        Location::Zone(player, ZoneKey::Desk),
+                    )
+                    .unwrap();
+            }
+        }
+        state
+    }
+
+    fn started(desk: usize) -> GameState {
+        let mut state = game(desk);
+        state
+            .apply(Action::StartMatch {
+                first: PlayerId::MY,
+            })
+            .unwrap();
+        state
+    }
+
+    fn advance_to(state: &mut GameState, phase: Phase) {
+        while state.phase != Some(phase) {
+            state
+                .apply(Action::AdvancePhase {
+                    player: state.active_player,
+                })
+                .unwrap();
+        }
+    }
+
+    // 被拒绝的操作不改变状态
+    fn assert_rejected(state: &mut GameState, action: Action, error: RuleError) {
+        let before = format!("{:?}", state);
+        assert_eq!(state.apply(action), Err(error));
+        assert_eq!(format!("{:?}", state), before);
+    }
+
+    #[test]
+    fn start_match() {
+        let mut state = game(3);
+        let events = state
+            .apply(Action::StartMatch {
+                first: PlayerId::OPPONENT,
+            })
+            .unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(
+            events.last(),
+            Some(&RuleEvent::PhaseStarted {
+                player: PlayerId::OPPONENT,
+                turn: 1,
+                phase: Phase::Draw,
+            })
+        );
+        assert_eq!(state.active_player, PlayerId::OPPONENT);
+        assert_eq!(state.phase, Some(Phase::Draw));
+        assert_rejected(
+            &mut state,
+            Action::StartMatch {
+                first: PlayerId::MY,
+            },
+            RuleError::MatchAlreadyStarted,
+        );
+    }
+
+    #[test]
+    fn draw() {
+        let mut state = game(1);
+        let top = state
+            .player(PlayerId::MY)
+            .unwrap()
+            .top(ZoneKey::Desk)
+            .unwrap();
+        let events = state
+            .apply(Action::Draw {
+                player: PlayerId::MY,
+            })
+            .unwrap();
+        assert_eq!(
+            events,
+            vec![RuleEvent::CardMoved {
+                card: top,
+                from: Location::Zone(PlayerId::MY, ZoneKey::Desk),
+                to: Location::Hand(PlayerId::MY),
+            }]
+        );
+        assert_eq!(state.player(PlayerId::MY).unwrap().hand, vec![top]);
+        // 对局开始前卡组为空时不能抽卡
+        assert_rejected(
+            &mut state,
+            Action::Draw {
+                player: PlayerId::MY,
+            },
+            RuleError::EmptyDesk(PlayerId::MY),
+        );
+    }
+
+    #[test]
+    fn draw_from_empty_desk_loses() {
+        let mut state = started(0);
+        let events = state
+            .apply(Action::Draw {
+                player: PlayerId::MY,
+            })
+            .unwrap();
+        assert_eq!(
+            events,
+            vec![RuleEvent::GameOver {
+                winner: Some(PlayerId::OPPONENT),
+                reason: GameOverReason::DeckOut,
+            }]
+        );
+        assert_rejected(
+            &mut state,
+            Action::Draw {
+                player: PlayerId::OPPONENT,
+            },
+            RuleError::GameOver,
+        );
+    }
+
+    #[test]
+    fn put_hand_in_lx() {
+        let mut state = game(3);
+        for _ in 0..3 {
+            state
+                .apply(Action::Draw {
+                    player: PlayerId::MY,
+                })
+                .unwrap();
+        }
+        let hand = state.player(PlayerId::MY).unwrap().hand.clone();
+        for card in &hand[..2] {
+            state
+                .apply(Action::PutHandInLx {
+                    player: PlayerId::MY,
+                    card: *card,
+                })
+                .unwrap();
+            assert_eq!(
+                state.card(*card).unwrap().location,
+                Location::Zone(PlayerId::MY, ZoneKey::Lx)
+            );
+            assert!(!state.card(*card).unwrap().pose.vertical);
+        }
+        assert_rejected(
+            &mut state,
+            Action::PutHandInLx {
+                player: PlayerId::MY,
+                card: hand[2],
+            },
+            RuleError::ZoneFull(PlayerId::MY, ZoneKey::Lx),
+        );
+        assert_rejected(
+            &mut state,
+            Action::PutHandInLx {
+                player: PlayerId::MY,
+                card: hand[0],
+            },
+            RuleError::NotInHand(hand[0]),
+        );
+    }
+
+    #[test]
+    fn missing_zone() {
+        // 棋盘上没有理性区
+        let mut state = GameState::with_seed(7);
+        let board: Vec<_> = board()
+            .into_iter()
+            .filter(|(key, _)| *key != ZoneKey::Lx)
+            .collect();
+        state.set_board(PlayerId::MY, &board).unwrap();
+        let card = state
+            .add_card(
+                PlayerId::MY,
+                info(CardType::Actor, 0),
+                Location::Hand(PlayerId::MY),
+            )
+            .unwrap();
+        assert_rejected(
+            &mut state,
+            Action::PutHandInLx {
+                player: PlayerId::MY,
+                card,
+            },
+            RuleError::UnknownZone(PlayerId::MY, ZoneKey::Lx),
+        );
+        assert_rejected(
+            &mut state,
+            Action::Effect {
+                source: card,
+                effect: Effect::MoveCard {
+                    card,
+                    to: Location::Zone(PlayerId::MY, ZoneKey::Lx),
+                },
+            },
+            RuleError::UnknownZone(PlayerId::MY, ZoneKey::Lx),
+        );
+    }
+
+    #[test]
+    fn put_desk_in_jq() {
+        let mut state = game(3);
+        for _ in 0..2 {
+            let top = state
+                .player(PlayerId::MY)
+                .unwrap()
+                .top(ZoneKey::Desk)
+                .unwrap();
+            state
+                .apply(Action::PutDeskInJq {
+                    player: PlayerId::MY,
+                })
+                .unwrap();
+            assert_eq!(
+                state.card(top).unwrap().location,
+                Location::Zone(PlayerId::MY, ZoneKey::Jq)
+            );
+        }
+        assert_rejected(
+            &mut state,
+            Action::PutDeskInJq {
+                player: PlayerId::MY,
+            },
+            RuleError::ZoneFull(PlayerId::MY, ZoneKey::Jq),
+        );
+        let mut state = game(0);
+        assert_rejected(
+            &mut state,
+            Action::PutDeskInJq {
+                player: PlayerId::MY,
+            },
+            RuleError::EmptyDesk(PlayerId::MY),
+        );
+    }
+
+    #[test]
+    fn recover_lx() {
+        let mut state = game(1);
+        state
+            .apply(Action::Draw {
+                player: PlayerId::MY,
+            })
+            .unwrap();
+        let card = state.player(PlayerId::MY).unwrap().hand[0];
+        state
+            .apply(Action::PutHandInLx {
+                player: PlayerId::MY,
+                card,
+            })
+            .unwrap();
+        // 第一次回复 横置的卡竖直
+        let events = state
+            .apply(Action::RecoverLx {
+                player: PlayerId::MY,
+            })
+            .unwrap();
+        assert_eq!(events, vec![RuleEvent::CardStood { card }]);
+        assert!(state.card(card).unwrap().pose.vertical);
+        // 第二次回复 竖直的卡回到手卡
+        state
+            .apply(Action::RecoverLx {
+                player: PlayerId::MY,
+            })
+            .unwrap();
+        assert_eq!(
+            state.card(card).unwrap().location,
+            Location::Hand(PlayerId::MY)
+        );
+        assert!(
+            state
+                .player(PlayerId::MY)
+                .unwrap()
+                .zone(ZoneKey::Lx)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn set_card() {
+        let mut state = started(0);
+        let actor = state
+            .add_card(
+                PlayerId::MY,
+                info(CardType::Actor, 0),
+                Location::Hand(PlayerId::MY),
+            )
+            .unwrap();
+        let other = state
+            .add_card(
+                PlayerId::MY,
+                info(CardType::Actor, 0),
+                Location::Hand(PlayerId::MY),
+            )
+            .unwrap();
+        let set = |card, zone| Action::SetCard {
+            player: PlayerId::MY,
+            card,
+            zone,
+            cost_hand: vec![],
+            cost_jq: vec![],
+        };
+        assert_rejected(
+            &mut state,
+            set(actor, ZoneKey::Battle(1)),
+            RuleError::WrongPhase(Phase::Draw),
+        );
+        advance_to(&mut state, Phase::Main);
+        let events = state.apply(set(actor, ZoneKey::Battle(1))).unwrap();
+        assert_eq!(
+            events.last(),
+            Some(&RuleEvent::CardEntered {
+                player: PlayerId::MY,
+                card: actor,
+                zone: ZoneKey::Battle(1),
+            })
+        );
+        assert_eq!(
+            state.card(actor).unwrap().location,
+            Location::Zone(PlayerId::MY, ZoneKey::Battle(1))
+        );
+        assert_rejected(
+            &mut state,
+            set(other, ZoneKey::Battle(1)),
+            RuleError::ZoneFull(PlayerId::MY, ZoneKey::Battle(1)),
+        );
+        // 人物不能放在安全屋
+        assert_rejected(
+            &mut state,
+            set(other, ZoneKey::Safe(1)),
+            RuleError::CannotSetOn(other, ZoneKey::Safe(1)),
+        );
+        assert_rejected(
+            &mut state,
+            set(actor, ZoneKey::Prepare),
+            RuleError::NotInHand(actor),
+        );
+    }
+
+    // 手卡中牺牲或洞悉的人物
+    fn replacing(state: &mut GameState, placement: Placement) -> CardId {
+        let info = info(CardType::Actor, 0).map(|info| CardInfo { placement, ..info });
+        state
+            .add_card(PlayerId::MY, info, Location::Hand(PlayerId::MY))
+            .unwrap()
+    }
+
+    fn set_on_prepare(card: CardId) -> Action {
+        Action::SetCard {
+            player: PlayerId::MY,
+            card,
+            zone: ZoneKey::Prepare,
+            cost_hand: vec![],
+            cost_jq: vec![],
+        }
+    }
+
+    #[test]
+    fn set_card_sacrifice() {
+        let mut state = started(0);
+        let old = state
+            .add_card(
+                PlayerId::MY,
+                info(CardType::Actor, 0),
+                Location::Zone(PlayerId::MY, ZoneKey::Prepare),
+            )
+            .unwrap();
+        let card = replacing(&mut state, Placement::Sacrifice);
+        advance_to(&mut state, Phase::Main);
+        state.apply(set_on_prepare(card)).unwrap();
+        // 原来的人物送去墓地
+        assert_eq!(
+            state.player(PlayerId::MY).unwrap().zone(ZoneKey::Prepare),
+            &[card]
+        );
+        assert_eq!(
+            state.card(old).unwrap().location,
+            Location::Zone(PlayerId::MY, ZoneKey::Grave)
+        );
+    }
+
+    #[test]
+    fn set_card_insight() {
+        let mut state = started(0);
+        let old = state
+            .add_card(
+                PlayerId::MY,
+                info(CardType::Actor, 0),
+                Location::Zone(PlayerId::MY, ZoneKey::Prepare),
+            )
+            .unwrap();
+        let card = replacing(&mut state, Placement::Insight);
+        advance_to(&mut state, Phase::Main);
+        state.apply(set_on_prepare(card)).unwrap();
+        // 叠放在原来的人物上面
+        assert_eq!(
+            state.player(PlayerId::MY).unwrap().zone(ZoneKey::Prepare),
+            &[old, card]
+        );
+        assert_eq!(
+            state.player(PlayerId::MY).unwrap().top(ZoneKey::Prepare),
+            Some(card)
+        );
+    }
+
+    #[test]
+    fn set_card_replace_needs_actor() {
+        let mut state = started(0);
+        // 区域中只有不是人物的卡
+        state
+            .add_card(
+                PlayerId::MY,
+                info(CardType::Arcane, 0),
+                Location::Zone(PlayerId::MY, ZoneKey::Prepare),
+            )
+            .unwrap();
+        let sacrifice = replacing(&mut state, Placement::Sacrifice);
+        let insight = replacing(&mut state, Placement::Insight);
+        advance_to(&mut state, Phase::Main);
+        assert_rejected(
+            &mut state,
+            set_on_prepare(sacrifice),
+            RuleError::CannotSetOn(sacrifice, ZoneKey::Prepare),
+        );
+        assert_rejected(
+            &mut state,
+            set_on_prepare(insight),
+            RuleError::CannotSetOn(insight, ZoneKey::Prepare),
+        );
+    }
+
+    fn meme(state: &mut GameState, ack: u32) -> CardId {
+        let info = info(CardType::Meme, 0).map(|info| CardInfo { ack, ..info });
+        state
+            .add_card(PlayerId::MY, info, Location::Hand(PlayerId::MY))
+            .unwrap()
+    }
+
+    #[test]
+    fn meme_attaches_to_actor() {
+        let mut state = started(0);
+        let host = actor(&mut state, PlayerId::MY, ZoneKey::Battle(1), 1000);
+        let card = meme(&mut state, 300);
+        let empty = meme(&mut state, 300);
+        advance_to(&mut state, Phase::Main);
+        // 没有人物的位置不能附着
+        assert_rejected(
+            &mut state,
+            Action::SetCard {
+                player: PlayerId::MY,
+                card: empty,
+                zone: ZoneKey::Prepare,
+                cost_hand: vec![],
+                cost_jq: vec![],
+            },
+            RuleError::CannotSetOn(empty, ZoneKey::Prepare),
+        );
+        let events = state
+            .apply(Action::SetCard {
+                player: PlayerId::MY,
+                card,
+                zone: ZoneKey::Battle(1),
+                cost_hand: vec![],
+                cost_jq: vec![],
+            })
+            .unwrap();
+        assert!(events.contains(&RuleEvent::CardAttached {
+            player: PlayerId::MY,
+            card,
+            host,
+        }));
+        assert_eq!(
+            state.card(card).unwrap().location,
+            Location::Attached(PlayerId::MY, host)
+        );
+        assert_eq!(
+            state.player(PlayerId::MY).unwrap().attachments(host),
+            &[card]
+        );
+        // 区域中仍然只有人物
+        assert_eq!(
+            state.player(PlayerId::MY).unwrap().zone(ZoneKey::Battle(1)),
+            &[host]
+        );
+        // 装备的攻击力加在人物上
+        assert_eq!(state.effective_ack(host), 1300);
+    }
+
+    #[test]
+    fn meme_leaves_with_host() {
+        let mut state = started(0);
+        let host = actor(&mut state, PlayerId::MY, ZoneKey::Battle(1), 1000);
+        let card = meme(&mut state, 300);
+        advance_to(&mut state, Phase::Main);
+        state
+            .apply(Action::SetCard {
+                player: PlayerId::MY,
+                card,
+                zone: ZoneKey::Battle(1),
+                cost_hand: vec![],
+                cost_jq: vec![],
+            })
+            .unwrap();
+        let events = state
+            .apply(Action::Effect {
+                source: card,
+                effect: Effect::Destroy { card: host },
+            })
+            .unwrap();
+        // 人物离场时装备一起送去墓地
+        assert!(events.contains(&RuleEvent::CardMoved {
+            card,
+            from: Location::Attached(PlayerId::MY, host),
+            to: Location::Zone(PlayerId::MY, ZoneKey::Grave),
+        }));
+        assert_eq!(
+            state.card(card).unwrap().location,
+            Location::Zone(PlayerId::MY, ZoneKey::Grave)
+        );
+        assert!(
+            state
+                .player(PlayerId::MY)
+                .unwrap()
+                .attachments(host)
+                .is_empty()
+        );
+        assert_eq!(
+            state.player(PlayerId::MY).unwrap().zone(ZoneKey::Grave),
+            &[host, card]
+        );
+    }
+
+    #[test]
+    fn set_card_cost() {
+        let mut state = started(0);
+        let card = state
+            .add_card(
+                PlayerId::MY,
+                info(CardType::Actor, 1),
+                Location::Hand(PlayerId::MY),
+            )
+            .unwrap();
+        let cost = state
+            .add_card(
+                PlayerId::MY,
+                info(CardType::Actor, 0),
+                Location::Hand(PlayerId::MY),
+            )
+            .unwrap();
+        advance_to(&mut state, Phase::Main);
+        let set = |cost_hand| Action::SetCard {
+            player: PlayerId::MY,
+            card,
+            zone: ZoneKey::Prepare,
+            cost_hand,
+            cost_jq: vec![],
+        };
+        assert_rejected(
+            &mut state,
+            set(vec![]),
+            RuleError::CostMismatch { need: 1, paid: 0 },
+        );
+        assert_rejected(&mut state, set(vec![card]), RuleError::DuplicateCard(card));
+        state.apply(set(vec![cost])).unwrap();
+        assert_eq!(
+            state.card(cost).unwrap().location,
+            Location::Zone(PlayerId::MY, ZoneKey::Lx)
+        );
+    }
+
+    #[test]
+    fn effect_draw() {
+        let mut state = game(2);
+        let source = state.player(PlayerId::MY).unwrap().zone(ZoneKey::Desk)[0];
+        let draw = |count| Action::Effect {
+            source,
+            effect: Effect::Draw {
+                player: PlayerId::MY,
+                count,
+            },
+        };
+        // 卡组不够时一张也不抽
+        assert_rejected(&mut state, draw(3), RuleError::EmptyDesk(PlayerId::MY));
+        assert_eq!(state.apply(draw(2)).unwrap().len(), 2);
+        assert_eq!(state.player(PlayerId::MY).unwrap().hand.len(), 2);
+
+        // 对局中抽完判负 已经抽到的卡和事件保留
+        let mut state = started(1);
+        let events = state.apply(draw(2)).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            events.last(),
+            Some(&RuleEvent::GameOver {
+                winner: Some(PlayerId::OPPONENT),
+                reason: GameOverReason::DeckOut,
+            })
+        );
+        assert_eq!(state.player(PlayerId::MY).unwrap().hand.len(), 1);
+    }
+
+    // 同样的种子和同样的操作得到同样的状态和随机数序列
+    #[test]
+    fn same_seed_same_match() {
+        let run = || {
+            let mut state = started(10);
+            state
+                .apply(Action::Draw {
+                    player: PlayerId::MY,
+                })
+                .unwrap();
+            state
+                .apply(Action::Shuffle {
+                    player: PlayerId::OPPONENT,
+                })
+                .unwrap();
+            state
+        };
+        let (mut a, mut b) = (run(), run());
+        assert_eq!(
+            a.player(PlayerId::MY).unwrap().zone(ZoneKey::Desk),
+            b.player(PlayerId::MY).unwrap().zone(ZoneKey::Desk)
+        );
+        assert_eq!(
+            a.player(PlayerId::OPPONENT).unwrap().zone(ZoneKey::Desk),
+            b.player(PlayerId::OPPONENT).unwrap().zone(ZoneKey::Desk)
+        );
+        assert_eq!(
+            a.player(PlayerId::MY).unwrap().hand,
+            b.player(PlayerId::MY).unwrap().hand
+        );
+        assert_eq!(a.rng, b.rng);
+        let stream = |state: &mut GameState| (0..20).map(|_| state.random(100)).collect::<Vec<_>>();
+        assert_eq!(stream(&mut a), stream(&mut b));
+
+        let mut other = game(10);
+        other.reseed(8).unwrap();
+        other
+            .apply(Action::StartMatch {
+                first: PlayerId::MY,
+            })
+            .unwrap();
+        assert_ne!(
+            other.player(PlayerId::MY).unwrap().zone(ZoneKey::Desk),
+            started(10)
+                .player(PlayerId::MY)
+                .unwrap()
+                .zone(ZoneKey::Desk)
+        );
+    }
+
+    #[test]
+    fn cast_arcane() {
+        let mut state = started(2);
+        let arcane = state
+            .add_card(
+                PlayerId::MY,
+                info(CardType::Arcane, 0),
+                Location::Hand(PlayerId::MY),
+            )
+            .unwrap();
+        let hand = state
+            .add_card(
+                PlayerId::OPPONENT,
+                info(CardType::Actor, 0),
+                Location::Hand(PlayerId::OPPONENT),
+            )
+            .unwrap();
+        let field = state
+            .add_card(
+                PlayerId::OPPONENT,
+                info(CardType::Actor, 0),
+                Location::Zone(PlayerId::OPPONENT, ZoneKey::Battle(1)),
+            )
+            .unwrap();
+        let desk = state
+            .player(PlayerId::OPPONENT)
+            .unwrap()
+            .zone(ZoneKey::Desk)[0];
+        advance_to(&mut state, Phase::Main);
+        let cast = |target| Action::CastArcane {
+            player: PlayerId::MY,
+            card: arcane,
+            target,
+            cost_hand: vec![],
+            cost_jq: vec![],
+        };
+        // 手卡和卡组中的卡不能作为目标
+        assert_rejected(&mut state, cast(Some(hand)), RuleError::NotOnField(hand));
+        assert_rejected(&mut state, cast(Some(desk)), RuleError::NotOnField(desk));
+        let events = state.apply(cast(Some(field))).unwrap();
+        assert_eq!(
+            events[0],
+            RuleEvent::ArcaneCast {
+                player: PlayerId::MY,
+                card: arcane,
+                target: Some(field),
+            }
+        );
+        assert_eq!(
+            state.card(arcane).unwrap().location,
+            Location::Zone(PlayerId::MY, ZoneKey::Grave)
+        );
+        // 墓地中的卡也不能作为目标
+        let other = state
+            .add_card(
+                PlayerId::MY,
+                info(CardType::Arcane, 0),
+                Location::Hand(PlayerId::MY),
+            )
+            .unwrap();
+        assert_rejected(
+            &mut state,
+            Action::CastArcane {
+                player: PlayerId::MY,
+                card: other,
+                target: Some(arcane),
+                cost_hand: vec![],
+                cost_jq: vec![],
+            },
+            RuleError::NotOnField(arcane),
+        );
+    }
+
+    #[test]
+    fn long_modifier() {
+        let mut state = started(1);
+        let card = state
+            .add_card(
+                PlayerId::MY,
+                info(CardType::Actor, 0),
+                Location::Zone(PlayerId::MY, ZoneKey::Battle(1)),
+            )
+            .unwrap();
+        // 持续回合数很大时不会溢出
+        let events = state
+            .apply(Action::Effect {
+                source: card,
+                effect: Effect::ModifyAck {
+                    card,
+                    delta: 100,
+                    duration: EffectDuration::Turns(u32::MAX),
+                },
+            })
+            .unwrap();
+        assert_eq!(events, vec![RuleEvent::AckModified { card, ack: 1100 }]);
+        assert_eq!(state.modifiers[0].until_turn, Some(u32::MAX));
+    }
+
+    // 放在场上的人物
+    fn actor(state: &mut GameState, player: PlayerId, zone: ZoneKey, ack: u32) -> CardId {
+        let info = info(CardType::Actor, 0).map(|info| CardInfo { ack, ..info });
+        state
+            .add_card(player, info, Location::Zone(player, zone))
+            .unwrap()
+    }
+
+    fn attack(attacker: CardId) -> Action {
+        Action::DeclareAttack {
+            player: PlayerId::MY,
+            attacker,
+        }
+    }
+
+    fn grave(player: PlayerId) -> Location {
+        Location::Zone(player, ZoneKey::Grave)
+    }
+
+    #[test]
+    fn attack_compares_ack() {
+        let mut state = started(1);
+        let attacker = actor(&mut state, PlayerId::MY, ZoneKey::Battle(1), 2000);
+        let defender = actor(&mut state, PlayerId::OPPONENT, ZoneKey::Battle(1), 1000);
+        advance_to(&mut state, Phase::Battle);
+        let events = state.apply(attack(attacker)).unwrap();
+        assert!(events.contains(&RuleEvent::BattleResolved {
+            attacker,
+            defender,
+            winner: Some(attacker),
+        }));
+        assert_eq!(
+            state.card(defender).unwrap().location,
+            grave(PlayerId::OPPONENT)
+        );
+        assert_eq!(
+            state.card(attacker).unwrap().location,
+            Location::Zone(PlayerId::MY, ZoneKey::Battle(1))
+        );
+
+        // 攻击力低的一方被破坏
+        let mut state = started(1);
+        let attacker = actor(&mut state, PlayerId::MY, ZoneKey::Battle(1), 500);
+        let defender = actor(&mut state, PlayerId::OPPONENT, ZoneKey::Battle(1), 1000);
+        advance_to(&mut state, Phase::Battle);
+        state.apply(attack(attacker)).unwrap();
+        assert_eq!(state.card(attacker).unwrap().location, grave(PlayerId::MY));
+        assert_eq!(
+            state.card(defender).unwrap().location,
+            Location::Zone(PlayerId::OPPONENT, ZoneKey::Battle(1))
+        );
+    }
+
+    #[test]
+    fn attack_tie_destroys_both() {
+        let mut state = started(1);
+        let attacker = actor(&mut state, PlayerId::MY, ZoneKey::Battle(1), 1000);
+        let defender = actor(&mut state, PlayerId::OPPONENT, ZoneKey::Battle(1), 1000);
+        advance_to(&mut state, Phase::Battle);
+        let events = state.apply(attack(attacker)).unwrap();
+        assert!(events.contains(&RuleEvent::BattleResolved {
+            attacker,
+            defender,
+            winner: None,
+        }));
+        assert_eq!(state.card(attacker).unwrap().location, grave(PlayerId::MY));
+        assert_eq!(
+            state.card(defender).unwrap().location,
+            grave(PlayerId::OPPONENT)
+        );
+    }
+
+    #[test]
+    fn unblocked_attack_damages_player() {
+        let mut state = started(1);
+        let attacker = actor(&mut state, PlayerId::MY, ZoneKey::Battle(1), 1500);
+        advance_to(&mut state, Phase::Battle);
+        let events = state.apply(attack(attacker)).unwrap();
+        assert_eq!(
+            events,
+            vec![
+                RuleEvent::AttackDeclared {
+                    player: PlayerId::MY,
+                    attacker,
+                    defender: None,
+                },
+                RuleEvent::PlayerDamaged {
+                    player: PlayerId::OPPONENT,
+                    amount: 1500,
+                    life: STARTING_LIFE - 1500,
+                },
+            ]
+        );
+        // 每个人物每回合只能攻击一次
+        assert_rejected(
+            &mut state,
+            attack(attacker),
+            RuleError::AlreadyAttacked(attacker),
+        );
+    }
+
+    #[test]
+    fn life_zero_loses() {
+        let mut state = started(1);
+        let attacker = actor(&mut state, PlayerId::MY, ZoneKey::Battle(1), 1500);
+        state.player_mut(PlayerId::OPPONENT).unwrap().life = 1000;
+        advance_to(&mut state, Phase::Battle);
+        let events = state.apply(attack(attacker)).unwrap();
+        assert_eq!(
+            events.last(),
+            Some(&RuleEvent::GameOver {
+                winner: Some(PlayerId::MY),
+                reason: GameOverReason::LifeZero,
+            })
+        );
+        assert_eq!(state.player(PlayerId::OPPONENT).unwrap().life, 0);
+        assert_rejected(&mut state, attack(attacker), RuleError::GameOver);
+    }
+
+    #[test]
+    fn attack_checks() {
+        let mut state = started(1);
+        let attacker = actor(&mut state, PlayerId::MY, ZoneKey::Battle(1), 1000);
+        let prepared = actor(&mut state, PlayerId::MY, ZoneKey::Prepare, 1000);
+        let enemy = actor(&mut state, PlayerId::OPPONENT, ZoneKey::Battle(1), 1000);
+        advance_to(&mut state, Phase::Main);
+        assert_rejected(
+            &mut state,
+            attack(attacker),
+            RuleError::WrongPhase(Phase::Main),
+        );
+        advance_to(&mut state, Phase::Battle);
+        // 只有自己战场上的人物可以攻击
+        assert_rejected(
+            &mut state,
+            attack(prepared),
+            RuleError::CannotAttack(prepared),
+        );
+        assert_rejected(&mut state, attack(enemy), RuleError::CannotAttack(enemy));
+        assert_rejected(
+            &mut state,
+            Action::DeclareAttack {
+                player: PlayerId::OPPONENT,
+                attacker: enemy,
+            },
+            RuleError::NotActivePlayer(PlayerId::OPPONENT),
+        );
+    }
+
+    #[test]
+    fn phase_checks() {
+        let mut state = game(2);
+        assert_rejected(
+            &mut state,
+            Action::AdvancePhase {
+                player: PlayerId::MY,
+            },
+            RuleError::MatchNotStarted,
+        );
+        state
+            .apply(Action::StartMatch {
+                first: PlayerId::MY,
+            })
+            .unwrap();
+        assert_rejected(
+            &mut state,
+            Action::AdvancePhase {
+                player: PlayerId::OPPONENT,
+            },
+            RuleError::NotActivePlayer(PlayerId::OPPONENT),
+        );
+        // 抽卡只在自己的抽卡阶段 回复只在回复阶段 放入理性区和激情区只在主要阶段
+        assert_rejected(
+            &mut state,
+            Action::Draw {
+                player: PlayerId::OPPONENT,
+            },
+            RuleError::NotActivePlayer(PlayerId::OPPONENT),
+        );
+        assert_rejected(
+            &mut state,
+            Action::RecoverLx {
+                player: PlayerId::MY,
+            },
+            RuleError::WrongPhase(Phase::Draw),
+        );
+        assert_rejected(
+            &mut state,
+            Action::PutDeskInJq {
+                player: PlayerId::MY,
+            },
+            RuleError::WrongPhase(Phase::Draw),
+        );
+        state
+            .apply(Action::Draw {
+                player: PlayerId::MY,
+            })
+            .unwrap();
+        let drawn = state.player(PlayerId::MY).unwrap().hand[0];
+        assert_rejected(
+            &mut state,
+            Action::PutHandInLx {
+                player: PlayerId::MY,
+                card: drawn,
+            },
+            RuleError::WrongPhase(Phase::Draw),
+        );
+        advance_to(&mut state, Phase::Recovery);
+        assert_rejected(
+            &mut state,
+            Action::Draw {
+                player: PlayerId::MY,
+            },
+            RuleError::WrongPhase(Phase::Recovery),
+        );
+        state
+            .apply(Action::RecoverLx {
+                player: PlayerId::MY,
+            })
+            .unwrap();
+        let card = state
+            .add_card(
+                PlayerId::OPPONENT,
+                info(CardType::Actor, 0),
+                Location::Hand(PlayerId::OPPONENT),
+            )
+            .unwrap();
+        advance_to(&mut state, Phase::Main);
+        assert_rejected(
+            &mut state,
+            Action::SetCard {
+                player: PlayerId::OPPONENT,
+                card,
+                zone: ZoneKey::Prepare,
+                cost_hand: vec![],
+                cost_jq: vec![],
+            },
+            RuleError::NotActivePlayer(PlayerId::OPPONENT),
+        );
+        // 回合结束后交换当前玩家
+        advance_to(&mut state, Phase::End);
+        state
+            .apply(Action::AdvancePhase {
+                player: PlayerId::MY,
+            })
+            .unwrap();
+        assert_eq!(state.active_player, PlayerId::OPPONENT);
+        assert_eq!(state.turn, 2);
+        assert_eq!(state.phase, Some(Phase::Draw));
+    }
+}
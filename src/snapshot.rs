@@ -0,0 +1,296 @@
+use crate::card_info::CardInfo;
+use crate::card_info::database::CardDatabase;
+use crate::card_zone::attach::AttachedTo;
+use crate::hand_card::CardLineResource;
+use crate::match_state::{CardEntityMap, CardInstanceId, MatchState};
+use crate::replay::ReplayRecorder;
+use crate::rules::state::GameState;
+use crate::rules::{CardId, CardPose, Location, PlayerId, ZoneKey};
+use crate::turn::TurnPhase;
+use crate::zone_info::ZoneRegistry;
+use bevy::prelude::*;
+use bevy_card3d_kit::prelude::card_state::CardState;
+use bevy_card3d_kit::prelude::{CardLine, HandCard, Moveable};
+use bevy_card3d_kit::zone::desk_zone::{DeskCard, DeskZone};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// 保存和读取对局
+/// 规则核心整体保存 实体用对局内的卡片编号代替 读取时重新生成卡片并建立新的对应关系
+
+// 默认的存档文件
+pub const DEFAULT_SAVE: &str = "saves/quicksave.ron";
+
+// 卡片实体上的信息
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedCard {
+    pub card: CardId,
+    pub info: CardInfo,
+    // 没有 CardState 时为空
+    pub state: Option<CardPose>,
+}
+
+// 场地中卡片的顺序 对应 DeskZone.card_list
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedZone {
+    pub player: PlayerId,
+    pub zone: ZoneKey,
+    pub cards: Vec<CardId>,
+}
+
+// 手卡的顺序 对应 CardLine.card_list
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedHand {
+    pub player: PlayerId,
+    pub cards: Vec<CardId>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MatchSnapshot {
+    // 包括回合 阶段和随机数的状态
+    pub state: GameState,
+    pub cards: Vec<SavedCard>,
+    pub zones: Vec<SavedZone>,
+    pub hands: Vec<SavedHand>,
+}
+
+impl MatchSnapshot {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        ron::from_str(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        if let Some(dir) = Path::new(path).parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", path, e))?;
+        }
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| format!("{}: {}", path, e))?;
+        std::fs::write(path, text).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+// 保存当前对局
+#[derive(Event, Clone, Debug)]
+pub struct SaveMatch {
+    pub path: String,
+}
+
+// 读取对局 替换当前所有的卡片
+#[derive(Event, Clone, Debug)]
+pub struct LoadMatch {
+    pub path: String,
+}
+
+// 正在恢复存档的阶段 进入阶段时不再自动抽卡和回复
+// 读取存档的那一帧设置 下一帧阶段切换完成后清除
+#[derive(Resource, Clone, Copy, Debug, Default)]
+pub struct RestoringMatch(pub bool);
+
+pub fn restoring_match(restoring: Res<RestoringMatch>) -> bool {
+    restoring.0
+}
+
+pub struct SnapshotPlugin;
+
+impl Plugin for SnapshotPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SaveMatch>();
+        app.add_event::<LoadMatch>();
+        app.init_resource::<RestoringMatch>();
+        app.add_systems(Update, (finish_restoring, save_match, load_match).chain());
+    }
+}
+
+fn finish_restoring(mut restoring: ResMut<RestoringMatch>) {
+    if restoring.0 {
+        restoring.0 = false;
+    }
+}
+
+fn save_match(
+    mut save_events: EventReader<SaveMatch>,
+    match_state: Res<MatchState>,
+    card_entity_map: Res<CardEntityMap>,
+    zone_registry: Res<ZoneRegistry>,
+    card_line_resource: Res<CardLineResource>,
+    query_cards: Query<(&CardInstanceId, &CardInfo, Option<&CardState>)>,
+    query_desks: Query<&DeskZone>,
+    query_card_line: Query<&CardLine>,
+) {
+    for save in save_events.read() {
+        // 实体换成卡片编号
+        let card_ids = |list: &[Entity]| -> Vec<CardId> {
+            list.iter()
+                .filter_map(|entity| card_entity_map.card(*entity))
+                .collect()
+        };
+        let mut cards: Vec<SavedCard> = query_cards
+            .iter()
+            .map(|(card, card_info, card_state)| SavedCard {
+                card: card.0,
+                info: card_info.clone(),
+                state: card_state.map(|state| CardPose {
+                    face_up: state.face_up,
+                    vertical: state.vertical,
+                }),
+            })
+            .collect();
+        cards.sort_by_key(|saved| saved.card);
+        let mut zones = vec![];
+        let mut hands = vec![];
+        for player in PlayerId::ALL {
+            for key in zone_registry.keys(player) {
+                let Some(desk) = zone_registry.zone(player, key) else {
+                    continue;
+                };
+                if let Ok(desk_zone) = query_desks.get(desk) {
+                    zones.push(SavedZone {
+                        player,
+                        zone: key,
+                        cards: card_ids(&desk_zone.card_list),
+                    });
+                }
+            }
+            if let Ok(card_line) = query_card_line.get(card_line_resource.card_line(player)) {
+                hands.push(SavedHand {
+                    player,
+                    cards: card_ids(&card_line.card_list),
+                });
+            }
+        }
+        let snapshot = MatchSnapshot {
+            state: match_state.0.clone(),
+            cards,
+            zones,
+            hands,
+        };
+        match snapshot.save(&save.path) {
+            Ok(()) => info!("match saved to {}", save.path),
+            Err(err) => warn!("save match failed: {}", err),
+        }
+    }
+}
+
+fn load_match(
+    mut commands: Commands,
+    mut load_events: EventReader<LoadMatch>,
+    asset_server: Res<AssetServer>,
+    card_database: Res<CardDatabase>,
+    zone_registry: Res<ZoneRegistry>,
+    card_line_resource: Res<CardLineResource>,
+    mut match_state: ResMut<MatchState>,
+    query_cards: Query<Entity, With<CardInstanceId>>,
+    mut query_desks: Query<&mut DeskZone>,
+    mut query_card_line: Query<&mut CardLine>,
+    mut next_phase: ResMut<NextState<TurnPhase>>,
+    mut restoring: ResMut<RestoringMatch>,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    for load in load_events.read() {
+        let snapshot = match MatchSnapshot::load(&load.path) {
+            Ok(snapshot) => snapshot,
+            Err(err) => {
+                warn!("load match failed: {}", err);
+                continue;
+            }
+        };
+        // 移除当前的卡片
+        for entity in query_cards.iter() {
+            commands.entity(entity).despawn();
+        }
+        for mut desk_zone in query_desks.iter_mut() {
+            desk_zone.card_list.clear();
+        }
+        for mut card_line in query_card_line.iter_mut() {
+            card_line.card_list.clear();
+        }
+
+        // 重新生成卡片 新的实体通过 CardInstanceId 登记
+        let mut spawned = HashMap::new();
+        for saved in snapshot.cards.iter() {
+            let Ok(instance) = snapshot.state.card(saved.card) else {
+                warn!("card {} is not in the saved match", saved.card);
+                continue;
+            };
+            let Some(mut entity) =
+                card_database.spawn(&mut commands, &asset_server, &saved.info.id)
+            else {
+                warn!("card {} is not in the card database", saved.info.id);
+                continue;
+            };
+            entity.insert((
+                saved.info.clone(),
+                instance.owner,
+                CardInstanceId(saved.card),
+            ));
+            if let Some(pose) = saved.state {
+                entity.insert(CardState {
+                    face_up: pose.face_up,
+                    vertical: pose.vertical,
+                });
+            }
+            spawned.insert(saved.card, entity.id());
+        }
+
+        // 按保存时的顺序放回场地和手卡
+        for saved_zone in snapshot.zones.iter() {
+            let Some(desk) = zone_registry.zone(saved_zone.player, saved_zone.zone) else {
+                warn!(
+                    "{:?} of {:?} is not on the board",
+                    saved_zone.zone, saved_zone.player
+                );
+                continue;
+            };
+            for card in saved_zone.cards.iter() {
+                if let Some(card_entity) = spawned.get(card).copied() {
+                    commands.entity(card_entity).insert(DeskCard {
+                        belongs_to_desk: Some(desk),
+                    });
+                }
+            }
+        }
+        for saved_hand in snapshot.hands.iter() {
+            let card_line_entity = card_line_resource.card_line(saved_hand.player);
+            for card in saved_hand.cards.iter() {
+                if let Some(card_entity) = spawned.get(card).copied() {
+                    commands
+                        .entity(card_entity)
+                        .insert(HandCard {
+                            belong_to_card_line: Some(card_line_entity),
+                        })
+                        .insert(Moveable);
+                }
+            }
+        }
+        for (card, instance) in snapshot.state.cards.iter() {
+            if let Location::Attached(_, host) = instance.location {
+                if let (Some(card_entity), Some(host_entity)) =
+                    (spawned.get(card).copied(), spawned.get(&host).copied())
+                {
+                    commands.entity(card_entity).insert(AttachedTo(host_entity));
+                }
+            }
+        }
+
+        // 阶段中的自动操作在保存前已经执行过
+        restoring.0 = true;
+        next_phase.set(
+            snapshot
+                .state
+                .phase
+                .map(TurnPhase::from)
+                .unwrap_or_default(),
+        );
+        info!(
+            "match loaded from {}: turn {} {:?}",
+            load.path, snapshot.state.turn, snapshot.state.phase
+        );
+        match_state.0 = snapshot.state;
+        if recorder.recording {
+            info!("replay recording stopped after loading a match");
+            recorder.stop();
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use crate::match_state::{GameAction, MatchEvent, MatchState};
+use crate::replay::ReplayPlayer;
+use crate::rules::{Action, Phase, PlayerId, RuleEvent};
+use crate::snapshot::restoring_match;
+use bevy::prelude::*;
+
+/// 回合与阶段
+/// 阶段的切换由规则核心决定 这里的状态只是同步 方便其他插件使用 OnEnter/OnExit
+
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TurnPhase {
+    // 对局还没开始
+    #[default]
+    Waiting,
+    Draw,
+    Recovery,
+    Main,
+    Battle,
+    End,
+}
+
+impl From<Phase> for TurnPhase {
+    fn from(phase: Phase) -> Self {
+        match phase {
+            Phase::Draw => TurnPhase::Draw,
+            Phase::Recovery => TurnPhase::Recovery,
+            Phase::Main => TurnPhase::Main,
+            Phase::Battle => TurnPhase::Battle,
+            Phase::End => TurnPhase::End,
+        }
+    }
+}
+
+// 阶段开始
+#[derive(Event, Clone, Debug)]
+pub struct PhaseStarted {
+    pub player: PlayerId,
+    pub turn: u32,
+    pub phase: Phase,
+}
+
+// 阶段结束
+#[derive(Event, Clone, Debug)]
+pub struct PhaseEnded {
+    pub player: PlayerId,
+    pub turn: u32,
+    pub phase: Phase,
+}
+
+pub struct TurnPlugin;
+
+impl Plugin for TurnPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<TurnPhase>();
+        app.add_event::<PhaseStarted>();
+        app.add_event::<PhaseEnded>();
+        app.add_systems(Update, sync_turn_phase);
+        // 回放时录像中已经有这些操作 读取存档时已经执行过
+        app.add_systems(
+            OnEnter(TurnPhase::Draw),
+            auto_draw.run_if(not(resource_exists::<ReplayPlayer>).and(not(restoring_match))),
+        );
+        app.add_systems(
+            OnEnter(TurnPhase::Recovery),
+            auto_recovery.run_if(not(resource_exists::<ReplayPlayer>).and(not(restoring_match))),
+        );
+        app.add_systems(
+            OnEnter(TurnPhase::End),
+            auto_end.run_if(not(resource_exists::<ReplayPlayer>).and(not(restoring_match))),
+        );
+    }
+}
+
+fn sync_turn_phase(
+    mut match_events: EventReader<MatchEvent>,
+    mut next_phase: ResMut<NextState<TurnPhase>>,
+    mut phase_started: EventWriter<PhaseStarted>,
+    mut phase_ended: EventWriter<PhaseEnded>,
+) {
+    for event in match_events.read() {
+        match event.0 {
+            RuleEvent::PhaseStarted {
+                player,
+                turn,
+                phase,
+            } => {
+                info!("turn {} {:?}: {:?} started", turn, player, phase);
+                next_phase.set(phase.into());
+                phase_started.write(PhaseStarted {
+                    player,
+                    turn,
+                    phase,
+                });
+            }
+            RuleEvent::PhaseEnded {
+                player,
+                turn,
+                phase,
+            } => {
+                phase_ended.write(PhaseEnded {
+                    player,
+                    turn,
+                    phase,
+                });
+            }
+            // 对局结束后不再自动推进阶段
+            RuleEvent::GameOver { winner, reason } => {
+                info!("game over: {:?} {:?}", winner, reason);
+                next_phase.set(TurnPhase::Waiting);
+            }
+            _ => {}
+        }
+    }
+}
+
+// 抽卡阶段 抽一张后进入回复阶段
+fn auto_draw(match_state: Res<MatchState>, mut actions: EventWriter<GameAction>) {
+    let player = match_state.active_player;
+    actions.write(GameAction(Action::Draw { player }));
+    actions.write(GameAction(Action::AdvancePhase { player }));
+}
+
+// 回复阶段 竖直的理性卡回到手卡 横置的竖直
+fn auto_recovery(match_state: Res<MatchState>, mut actions: EventWriter<GameAction>) {
+    let player = match_state.active_player;
+    actions.write(GameAction(Action::RecoverLx { player }));
+    actions.write(GameAction(Action::AdvancePhase { player }));
+}
+
+// 结束阶段 直接交给对手
+fn auto_end(match_state: Res<MatchState>, mut actions: EventWriter<GameAction>) {
+    let player = match_state.active_player;
+    actions.write(GameAction(Action::AdvancePhase { player }));
+}
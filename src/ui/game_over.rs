@@ -0,0 +1,194 @@
+use crate::match_state::{MatchEvent, MatchState};
+use crate::rules::{GameOverReason, PlayerId, RuleEvent};
+use bevy::color;
+use bevy::prelude::*;
+
+/// 生命显示和对局结果
+
+// 对局结束
+#[derive(Event, Clone, Debug)]
+pub struct GameOver {
+    // 为空时是平局
+    pub winner: Option<PlayerId>,
+    pub reason: GameOverReason,
+}
+
+// 生命显示
+#[derive(Component, Clone, Debug)]
+pub struct LifeText;
+
+#[derive(Component, Clone, Debug)]
+pub struct GameOverShow;
+
+pub struct GameOverPlugin;
+
+impl Plugin for GameOverPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<GameOver>();
+        app.add_systems(Startup, setup);
+        app.add_systems(
+            Update,
+            (emit_game_over, update_life_text, show_game_over).chain(),
+        );
+    }
+}
+
+fn setup(mut commands: Commands) {
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::FlexEnd,
+                align_items: AlignItems::FlexStart,
+                ..Default::default()
+            },
+            Pickable::IGNORE,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                LifeText,
+                Node {
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..Default::default()
+                },
+                Text::new(""),
+                TextFont {
+                    font_size: 20.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+                BackgroundColor(color::palettes::css::DARK_GRAY.with_alpha(0.6).into()),
+            ));
+        });
+}
+
+fn emit_game_over(mut match_events: EventReader<MatchEvent>, mut game_over: EventWriter<GameOver>) {
+    for event in match_events.read() {
+        if let RuleEvent::GameOver { winner, reason } = event.0 {
+            game_over.write(GameOver { winner, reason });
+        }
+    }
+}
+
+fn update_life_text(match_state: Res<MatchState>, mut query: Query<&mut Text, With<LifeText>>) {
+    if !match_state.is_changed() {
+        return;
+    }
+    let life = |player| match_state.player(player).map(|p| p.life).unwrap_or(0);
+    for mut text in query.iter_mut() {
+        text.0 = format!(
+            "Opponent Life: {}\nMy Life: {}",
+            life(PlayerId::OPPONENT),
+            life(PlayerId::MY)
+        );
+    }
+}
+
+// 显示对局结果
+fn show_game_over(
+    mut commands: Commands,
+    mut game_over: EventReader<GameOver>,
+    query_show: Query<Entity, With<GameOverShow>>,
+) {
+    for event in game_over.read() {
+        for entity in query_show.iter() {
+            commands.entity(entity).despawn();
+        }
+        let title = match event.winner {
+            Some(PlayerId::MY) => "You Win".to_string(),
+            Some(_) => "You Lose".to_string(),
+            None => "Draw".to_string(),
+        };
+        let reason = match event.reason {
+            GameOverReason::LifeZero => "Life reached zero",
+            GameOverReason::DeckOut => "Desk is empty",
+        };
+        commands
+            .spawn((
+                GameOverShow,
+                Node {
+                    height: Val::Percent(100.0),
+                    width: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
+                BackgroundColor(Color::BLACK.with_alpha(0.5)),
+            ))
+            .with_children(|parent| {
+                parent
+                    .spawn((
+                        Node {
+                            width: Val::Percent(50.),
+                            height: Val::Auto,
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            padding: UiRect::all(Val::Px(16.0)),
+                            row_gap: Val::Px(10.0),
+                            ..default()
+                        },
+                        BackgroundColor(
+                            color::palettes::css::LIGHT_SKY_BLUE.with_alpha(0.5).into(),
+                        ),
+                        Outline {
+                            width: Val::Px(6.0),
+                            offset: Default::default(),
+                            color: color::palettes::css::DARK_BLUE.into(),
+                        },
+                    ))
+                    .with_children(|dialog| {
+                        dialog.spawn((
+                            Text::new(title),
+                            TextFont {
+                                font_size: 48.0,
+                                ..default()
+                            },
+                            TextColor(Color::BLACK),
+                        ));
+                        dialog.spawn((
+                            Text::new(reason),
+                            TextFont {
+                                font_size: 24.0,
+                                ..default()
+                            },
+                            TextColor(Color::BLACK),
+                        ));
+                        dialog
+                            .spawn((
+                                Button,
+                                Node {
+                                    width: Val::Percent(60.),
+                                    height: Val::Px(40.),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    border: UiRect::all(Val::Px(2.0)),
+                                    ..Default::default()
+                                },
+                                BorderColor(Color::WHITE),
+                                BackgroundColor(color::palettes::css::GREEN.into()),
+                            ))
+                            .with_children(|button| {
+                                button.spawn((
+                                    Text::new("Close"),
+                                    TextFont {
+                                        font_size: 20.0,
+                                        ..default()
+                                    },
+                                    TextColor(Color::srgb(0.9, 0.9, 0.9)),
+                                ));
+                            })
+                            .observe(
+                                |_click: Trigger<Pointer<Click>>,
+                                 mut commands: Commands,
+                                 query_show: Query<Entity, With<GameOverShow>>| {
+                                    for entity in query_show.iter() {
+                                        commands.entity(entity).despawn();
+                                    }
+                                },
+                            );
+                    });
+            });
+    }
+}
@@ -1,4 +1,5 @@
 use crate::card_info::CardInfo;
+use crate::match_state::CardInstanceId;
 use crate::zone_info::ZoneInfo;
 use bevy::color;
 use bevy::color::palettes::css::{GRAY, GREEN};
@@ -15,9 +16,11 @@ use bevy_card3d_kit::zone::desk_zone::DeskZone;
 use std::cmp::PartialEq;
 use std::sync::Arc;
 
-// 被UI中选中
+pub mod game_over;
+
+// 被UI中选中 记录所在的区域
 #[derive(Component, Clone, Debug)]
-pub struct UIChose(pub u32);
+pub struct UIChose(pub Entity);
 
 // 确认按钮组件
 #[derive(Component, Clone, Debug)]
@@ -39,8 +42,10 @@ fn confirm_button_color_system(
         let num = ui_chose_query.iter().len();
         let mut check_a = true;
         for zone_and_limit in confirm_button.list.iter() {
-            let index = zone_and_limit.entity.index();
-            let i = ui_chose_query.iter().filter(|&x| x.0 == index).count();
+            let i = ui_chose_query
+                .iter()
+                .filter(|&x| x.0 == zone_and_limit.entity)
+                .count();
             if i >= zone_and_limit.min && i <= zone_and_limit.max {
                 // do nothing
             } else {
@@ -77,7 +82,7 @@ pub struct UICardInfo {
 }
 
 // 位置卡片对
-pub type ZoneCardPair = (Entity, Entity);
+pub type ZoneCardPair = (Entity, CardInstanceId);
 
 #[derive(Clone, Debug)]
 pub struct ZoneAndLimit {
@@ -99,15 +104,27 @@ pub struct ShowDialogBox<T> {
     pub min: usize,
     pub max: usize,
     // 生成事件的回调
-    pub callback:
-        Arc<dyn Fn(Entity, Vec<ZoneCardPair>, Vec<ZoneCardPair>) -> T + Send + Sync + 'static>,
+    pub callback: DialogCallback<T>,
 }
 
+// 对话框确认后生成事件的回调
+pub type DialogCallback<T> =
+    Arc<dyn Fn(Entity, Vec<ZoneCardPair>, Vec<ZoneCardPair>) -> T + Send + Sync + 'static>;
+
 #[derive(Event, Clone, Debug)]
 pub enum EnterEvent {
     Test,
     SetCard {
         card: Entity,
+        // 登场的位置
+        zone: Entity,
+        cost_hand: Vec<ZoneCardPair>,
+        cost_jq: Vec<ZoneCardPair>,
+    },
+    CastArcane {
+        card: Entity,
+        // 目标卡片
+        target: Option<Entity>,
         cost_hand: Vec<ZoneCardPair>,
         cost_jq: Vec<ZoneCardPair>,
     },
@@ -142,7 +159,7 @@ fn show_dialog(
     // 查询手卡
     mut query_card_line: Query<&mut CardLine>,
     // 查询卡片信息
-    mut query_card: Query<(&CardInfo, &CardState)>,
+    mut query_card: Query<(&CardInfo, &CardState, &CardInstanceId)>,
     asset_server: Res<AssetServer>,
 ) {
     for dialog_box in show_dialog.read() {
@@ -164,12 +181,12 @@ fn show_dialog(
             if let Ok(card_line) = query_card_line.get(zone_and_limit.entity.clone()) {
                 let mut info_list = vec![];
                 for card_entity in card_line.card_list.iter() {
-                    if let Ok((card_info, card_state)) = query_card.get(*card_entity) {
+                    if let Ok((card_info, card_state, card)) = query_card.get(*card_entity) {
                         let ui_card_info = UICardInfo {
                             card_type: UICardType::Hand,
                             card_info: card_info.clone(),
                             card_state: card_state.clone(),
-                            zone_card_pair: (zone_and_limit.clone().entity, card_entity.clone()),
+                            zone_card_pair: (zone_and_limit.clone().entity, *card),
                         };
                         if *card_entity != box_card_entity {
                             info_list.push(ui_card_info);
@@ -187,12 +204,12 @@ fn show_dialog(
             {
                 let mut info_list = vec![];
                 for card_entity in desk_zone.card_list.iter() {
-                    if let Ok((card_info, card_state)) = query_card.get(*card_entity) {
+                    if let Ok((card_info, card_state, card)) = query_card.get(*card_entity) {
                         let ui_card_info = UICardInfo {
                             card_type: UICardType::Zone,
                             card_info: card_info.clone(),
                             card_state: card_state.clone(),
-                            zone_card_pair: (zone_and_limit.clone().entity, card_entity.clone()),
+                            zone_card_pair: (zone_and_limit.clone().entity, *card),
                         };
                         info_list.push(ui_card_info);
                     }
@@ -249,7 +266,7 @@ fn show_dialog(
                         .collect();
                     let event = (cb)(box_card_entity, hand, zone);
                     info!("Sending {:?}", event);
-                    // enter_events.write(event);
+                    enter_events.write(event);
                     if let Ok(single) = dialog_show.single() {
                         commands.entity(single).despawn();
                     }
@@ -422,7 +439,7 @@ fn spawn_card_list(
     list: Vec<UICardInfo>,
     zone_and_limit: ZoneAndLimit,
 ) {
-    let entity_index = zone_and_limit.clone().entity.index();
+    let zone_entity = zone_and_limit.entity;
     parent
         .spawn((
             Node {
@@ -540,7 +557,7 @@ fn spawn_card_list(
                                                 offset: Val::Px(0.2),
                                                 color: color::palettes::css::RED.into(),
                                             })
-                                            .insert(UIChose(entity_index));
+                                            .insert(UIChose(zone_entity));
                                     }
                                 },
                             );
@@ -1,20 +1,21 @@
-use crate::card_info::card_enums::CardType;
 use crate::card_zone::can_set::CanSetOn;
+use crate::rules::{PlayerId, ZoneKey, ZoneRule};
+use crate::zone_layout::BoardLayout;
 use bevy::prelude::*;
-use bevy_card3d_kit::prelude::card_state::CardState;
 use bevy_card3d_kit::zone::desk_zone::DeskZone;
 use bevy_card3d_kit::zone::{Zone, ZoneMaterialGetter, bind_zone_render};
-use std::f32::consts::PI;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::str::FromStr;
 
 /// 场地信息的定义
 
 #[derive(Clone, Debug, Component)]
 pub struct ZoneInfo {
     pub zone_type: ZoneType,
-    pub opponent: bool,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
 pub enum ZoneType {
     Nothing,
     // 战场
@@ -33,6 +34,54 @@ pub enum ZoneType {
     GraveField,
 }
 
+impl ZoneType {
+    // 对应规则核心的区域 战场和安全屋需要编号
+    pub fn key(&self, slot: Option<usize>) -> Option<ZoneKey> {
+        match (self, slot) {
+            (ZoneType::BattleField, Some(slot)) => Some(ZoneKey::Battle(slot)),
+            (ZoneType::SafeField, Some(slot)) => Some(ZoneKey::Safe(slot)),
+            (ZoneType::PreparationField, _) => Some(ZoneKey::Prepare),
+            (ZoneType::LxField, _) => Some(ZoneKey::Lx),
+            (ZoneType::JqField, _) => Some(ZoneKey::Jq),
+            (ZoneType::DeskField, _) => Some(ZoneKey::Desk),
+            (ZoneType::GraveField, _) => Some(ZoneKey::Grave),
+            _ => None,
+        }
+    }
+}
+
+impl From<ZoneKey> for ZoneType {
+    fn from(key: ZoneKey) -> Self {
+        match key {
+            ZoneKey::Desk => ZoneType::DeskField,
+            ZoneKey::Grave => ZoneType::GraveField,
+            ZoneKey::Lx => ZoneType::LxField,
+            ZoneKey::Jq => ZoneType::JqField,
+            ZoneKey::Battle(_) => ZoneType::BattleField,
+            ZoneKey::Prepare => ZoneType::PreparationField,
+            ZoneKey::Safe(_) => ZoneType::SafeField,
+        }
+    }
+}
+
+impl FromStr for ZoneType {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Nothing" => Ok(ZoneType::Nothing),
+            "BattleField" => Ok(ZoneType::BattleField),
+            "PreparationField" => Ok(ZoneType::PreparationField),
+            "SafeField" => Ok(ZoneType::SafeField),
+            "LxField" => Ok(ZoneType::LxField),
+            "JqField" => Ok(ZoneType::JqField),
+            "DeskField" => Ok(ZoneType::DeskField),
+            "GraveField" => Ok(ZoneType::GraveField),
+            _ => Err(()),
+        }
+    }
+}
+
 impl ZoneMaterialGetter for ZoneInfo {
     fn get_mal(
         &self,
@@ -78,480 +127,153 @@ impl Plugin for ZoneInfoPlugin {
 }
 
 fn setup(mut commands: Commands) {
-    render_all_zone(&mut commands, 4.0, 1.2);
+    let layout = BoardLayout::load();
+    let registry = spawn_layout(&mut commands, &layout);
+    commands.insert_resource(registry);
 }
 
-fn render_all_zone(commands: &mut Commands, a: f32, mid: f32) {
-    let half_a = a * 0.5;
-    let half_mid = mid * 0.5;
-    let mid_point = Transform::default();
-
-    // 加载中心块
-    commands.spawn((
-        Name::new("Mid Zone"),
-        Zone {
-            center: mid_point.clone(),
-            size: Vec2::new(a * 6.0 - 0.1, mid) - 0.1,
-        },
-        ZoneInfo {
-            zone_type: ZoneType::Nothing,
-            opponent: false,
-        },
-    ));
-    // ==============对手===================
-    //对方准备区
-    let opponent_prepare = commands
-        .spawn((
-            Name::new("opponent Prepare Zone"),
-            Zone {
-                center: Transform::from_xyz(-3.0 * half_a, half_mid + half_a, 0.0),
-                size: Vec2::new(a - 0.1, a - 0.1),
-            },
-            ZoneInfo {
-                zone_type: ZoneType::PreparationField,
-                opponent: true,
-            },
-            DeskZone::default(),
-        ))
-        .id();
-    // 对方三个战场
-    let opponent_battle1 = commands
-        .spawn((
-            Name::new("opponent Battle Zone 1"),
-            Zone {
-                center: Transform::from_xyz(-1.0 * half_a, half_mid + half_a, 0.0),
-                size: Vec2::new(a - 0.1, a - 0.1),
-            },
-            ZoneInfo {
-                zone_type: ZoneType::BattleField,
-                opponent: true,
-            },
-            DeskZone::default(),
-        ))
-        .id();
-    let opponent_battle2 = commands
-        .spawn((
-            Name::new("opponent Battle Zone 2"),
-            Zone {
-                center: Transform::from_xyz(half_a, half_mid + half_a, 0.0),
-                size: Vec2::new(a - 0.1, a - 0.1),
-            },
-            ZoneInfo {
-                zone_type: ZoneType::BattleField,
-                opponent: true,
-            },
-            DeskZone::default(),
-        ))
-        .id();
-    let opponent_battle3 = commands
-        .spawn((
-            Name::new("opponent Battle Zone 3"),
-            Zone {
-                center: Transform::from_xyz(3.0 * half_a, half_mid + half_a, 0.0),
-                size: Vec2::new(a - 0.1, a - 0.1),
-            },
-            ZoneInfo {
-                zone_type: ZoneType::BattleField,
-                opponent: true,
-            },
-            DeskZone::default(),
-        ))
-        .id();
-    //上方安全屋 x4
-    let opponent_safe1 = commands
-        .spawn((
-            Name::new("opponent Safe Zone 1"),
-            Zone {
-                center: Transform::from_xyz(-3.0 * half_a, half_mid + half_a + a, 0.0),
-                size: Vec2::new(a - 0.1, a - 0.1),
-            },
-            ZoneInfo {
-                zone_type: ZoneType::SafeField,
-                opponent: true,
-            },
-            DeskZone::default(),
-        ))
-        .id();
-    let opponent_safe2 = commands
-        .spawn((
-            Name::new("opponent Safe Zone 2"),
-            Zone {
-                center: Transform::from_xyz(-1.0 * half_a, half_mid + half_a + a, 0.0),
-                size: Vec2::new(a - 0.1, a - 0.1),
-            },
-            ZoneInfo {
-                zone_type: ZoneType::SafeField,
-                opponent: true,
-            },
-            DeskZone::default(),
-        ))
-        .id();
-    let opponent_safe3 = commands
-        .spawn((
-            Name::new("opponent Safe Zone 3"),
-            Zone {
-                center: Transform::from_xyz(half_a, half_mid + half_a + a, 0.0),
-                size: Vec2::new(a - 0.1, a - 0.1),
-            },
-            ZoneInfo {
-                zone_type: ZoneType::SafeField,
-                opponent: true,
-            },
-            DeskZone::default(),
-        ))
-        .id();
-    let opponent_safe4 = commands
-        .spawn((
-            Name::new("opponent Safe Zone 4"),
-            Zone {
-                center: Transform::from_xyz(3.0 * half_a, half_mid + half_a + a, 0.0),
-                size: Vec2::new(a - 0.1, a - 0.1),
-            },
-            ZoneInfo {
-                zone_type: ZoneType::SafeField,
-                opponent: true,
-            },
-            DeskZone::default(),
-        ))
-        .id();
-    // 上方理性区
-    let opponent_lx = commands
-        .spawn((
-            Name::new("opponent LX Zone"),
-            Zone {
-                center: Transform::from_xyz(-5.0 * half_a, half_mid + a, 0.0)
-                    .with_rotation(Quat::from_axis_angle(Vec3::Z, PI)),
-                size: Vec2::new(a - 0.1, a * 2.0 - 0.1),
-            },
-            ZoneInfo {
-                zone_type: ZoneType::LxField,
-                opponent: true,
-            },
-            DeskZone {
-                card_list: vec![],
-                opt_capacity: Some(6),
-            },
-            CardState {
-                face_up: true,
-                vertical: false,
-            },
-        ))
-        .id();
-    // 上方激情区
-    let opponent_jq = commands
-        .spawn((
-            Name::new("opponent JQ Zone"),
+// 按布局生成所有场地
+fn spawn_layout(commands: &mut Commands, layout: &BoardLayout) -> ZoneRegistry {
+    let mut registry = ZoneRegistry::default();
+    for zone in layout.zones.iter() {
+        let mut entity = commands.spawn((
+            Name::new(zone.name.clone()),
             Zone {
-                center: Transform::from_xyz(5.0 * half_a, half_mid + a, 0.0)
-                    .with_rotation(Quat::from_axis_angle(Vec3::Z, PI)),
-                size: Vec2::new(a - 0.1, a * 2.0 - 0.1),
+                center: layout.transform(zone),
+                size: layout.size(zone),
             },
             ZoneInfo {
-                zone_type: ZoneType::JqField,
-                opponent: true,
-            },
-            DeskZone {
+                zone_type: zone.zone_type,
+            },
+        ));
+        if zone.zone_type != ZoneType::Nothing {
+            // 场上的区域可以牺牲 洞悉和附着 叠放的卡片数量只由规则核心检查
+            let is_field = zone
+                .zone_type
+                .key(zone.slot)
+                .is_some_and(|key| key.is_field());
+            entity.insert(DeskZone {
                 card_list: vec![],
-                opt_capacity: Some(6),
-            },
-            CardState {
-                face_up: false,
-                vertical: false,
-            },
-        ))
-        .id();
-
-    // 上方卡组
-    let opponent_desk = commands
-        .spawn((
-            Name::new("opponent Desk Zone"),
-            DeskZone::default(),
-            Zone {
-                center: Transform::from_xyz(-7.0 * half_a, half_mid + half_a, 0.0)
-                    .with_rotation(Quat::from_axis_angle(Vec3::Z, PI)),
-                size: Vec2::new(a - 0.1, a - 0.1),
-            },
-            ZoneInfo {
-                zone_type: ZoneType::DeskField,
-                opponent: true,
-            },
-            CardState {
-                face_up: false,
-                vertical: true,
-            },
-        ))
-        .id();
-    // 上方墓地
-    let opponent_grave = commands
-        .spawn((
-            Name::new("opponent GraveField Zone"),
-            Zone {
-                center: Transform::from_xyz(-7.0 * half_a, half_mid + half_a + a, 0.0)
-                    .with_rotation(Quat::from_axis_angle(Vec3::Z, PI)),
-                size: Vec2::new(a - 0.1, a - 0.1),
-            },
-            ZoneInfo {
-                zone_type: ZoneType::GraveField,
-                opponent: true,
-            },
-            DeskZone::default(),
-        ))
-        .id();
-    // ==============自己===================
-    //准备区
-    let prepare = commands
-        .spawn((
-            Name::new("Prepare Zone"),
-            Zone {
-                center: Transform::from_xyz(3.0 * half_a, -(half_mid + half_a), 0.0),
-                size: Vec2::new(a - 0.1, a - 0.1),
-            },
-            ZoneInfo {
-                zone_type: ZoneType::PreparationField,
-                opponent: false,
-            },
-            DeskZone::default(),
-            CanSetOn(vec![CardType::Actor]),
-        ))
-        .id();
-    // 三个战场
-    let battle1 = commands
-        .spawn((
-            Name::new("Battle Zone 1"),
-            Zone {
-                center: Transform::from_xyz(-1.0 * half_a, -(half_mid + half_a), 0.0),
-                size: Vec2::new(a - 0.1, a - 0.1),
-            },
-            ZoneInfo {
-                zone_type: ZoneType::BattleField,
-                opponent: false,
-            },
-            DeskZone::default(),
-            CanSetOn(vec![CardType::Actor]),
-        ))
-        .id();
-    let battle2 = commands
-        .spawn((
-            Name::new("Battle Zone 2"),
-            Zone {
-                center: Transform::from_xyz(half_a, -(half_mid + half_a), 0.0),
-                size: Vec2::new(a - 0.1, a - 0.1),
-            },
-            ZoneInfo {
-                zone_type: ZoneType::BattleField,
-                opponent: false,
-            },
-            DeskZone::default(),
-            CanSetOn(vec![CardType::Actor]),
-        ))
-        .id();
-    let battle3 = commands
-        .spawn((
-            Name::new("Battle Zone 3"),
-            Zone {
-                center: Transform::from_xyz(-3.0 * half_a, -(half_mid + half_a), 0.0),
-                size: Vec2::new(a - 0.1, a - 0.1),
-            },
-            ZoneInfo {
-                zone_type: ZoneType::BattleField,
-                opponent: false,
-            },
-            DeskZone::default(),
-            CanSetOn(vec![CardType::Actor]),
-        ))
-        .id();
-    //安全屋 x4
-    let safe1 = commands
-        .spawn((
-            Name::new("Safe Zone 1"),
-            Zone {
-                center: Transform::from_xyz(-3.0 * half_a, -(half_mid + half_a + a), 0.0),
-                size: Vec2::new(a - 0.1, a - 0.1),
-            },
-            ZoneInfo {
-                zone_type: ZoneType::SafeField,
-                opponent: false,
-            },
-            DeskZone::default(),
-            CanSetOn(vec![CardType::Meme]),
-        ))
-        .id();
-    let safe2 = commands
-        .spawn((
-            Name::new("Safe Zone 2"),
-            Zone {
-                center: Transform::from_xyz(-1.0 * half_a, -(half_mid + half_a + a), 0.0),
-                size: Vec2::new(a - 0.1, a - 0.1),
-            },
-            ZoneInfo {
-                zone_type: ZoneType::SafeField,
-                opponent: false,
-            },
-            DeskZone::default(),
-            CanSetOn(vec![CardType::Meme]),
-        ))
-        .id();
-    let safe3 = commands
-        .spawn((
-            Name::new("Safe Zone 3"),
-            Zone {
-                center: Transform::from_xyz(half_a, -(half_mid + half_a + a), 0.0),
-                size: Vec2::new(a - 0.1, a - 0.1),
-            },
-            ZoneInfo {
-                zone_type: ZoneType::SafeField,
-                opponent: false,
-            },
-            DeskZone::default(),
-            CanSetOn(vec![CardType::Meme]),
-        ))
-        .id();
-    let safe4 = commands
-        .spawn((
-            Name::new("Safe Zone 4"),
-            Zone {
-                center: Transform::from_xyz(3.0 * half_a, -(half_mid + half_a + a), 0.0),
-                size: Vec2::new(a - 0.1, a - 0.1),
-            },
-            ZoneInfo {
-                zone_type: ZoneType::SafeField,
-                opponent: false,
-            },
-            DeskZone::default(),
-            CanSetOn(vec![CardType::Meme]),
-        ))
-        .id();
-    // 理性区
-    let lx = commands
-        .spawn((
-            Name::new("LX Zone"),
-            Zone {
-                center: Transform::from_xyz(5.0 * half_a, -(half_mid + a), 0.0),
-                size: Vec2::new(a - 0.1, a * 2.0 - 0.1),
-            },
-            ZoneInfo {
-                zone_type: ZoneType::LxField,
-                opponent: false,
-            },
-            DeskZone {
-                card_list: vec![],
-                opt_capacity: Some(6),
-            },
-            CardState {
-                face_up: true,
-                vertical: false,
-            },
-        ))
-        .id();
-    // 激情区
-    let jq = commands
-        .spawn((
-            Name::new("JQ Zone"),
-            Zone {
-                center: Transform::from_xyz(-5.0 * half_a, -(half_mid + a), 0.0),
-                size: Vec2::new(a - 0.1, a * 2.0 - 0.1),
-            },
-            ZoneInfo {
-                zone_type: ZoneType::JqField,
-                opponent: false,
-            },
-            DeskZone {
-                card_list: vec![],
-                opt_capacity: Some(6),
-            },
-            CardState {
-                face_up: false,
-                vertical: false,
-            },
-        ))
-        .id();
-
-    // 卡组
-    let desk = commands
-        .spawn((
-            Name::new("Desk Zone"),
-            DeskZone::default(),
-            Zone {
-                center: Transform::from_xyz(7.0 * half_a, -(half_mid + half_a), 0.0)
-                    .with_rotation(Quat::from_axis_angle(Vec3::Z, PI)),
-                size: Vec2::new(a - 0.1, a - 0.1),
-            },
-            ZoneInfo {
-                zone_type: ZoneType::DeskField,
-                opponent: true,
-            },
-            CardState {
-                face_up: false,
-                vertical: true,
-            },
-        ))
-        .id();
-    // 墓地
-    let grave = commands
-        .spawn((
-            Name::new("GraveField Zone"),
-            Zone {
-                center: Transform::from_xyz(7.0 * half_a, -(half_mid + half_a + a), 0.0)
-                    .with_rotation(Quat::from_axis_angle(Vec3::Z, PI)),
-                size: Vec2::new(a - 0.1, a - 0.1),
-            },
-            ZoneInfo {
-                zone_type: ZoneType::GraveField,
-                opponent: true,
-            },
-            DeskZone::default(),
-        ))
-        .id();
-    commands.insert_resource(AllZoneInfoResource {
-        my: AllZoneInfo {
-            desk: desk,
-            grave: grave,
-            lx: lx,
-            jq: jq,
-            battle1: battle1,
-            battle2: battle2,
-            battle3: battle3,
-            prepare: prepare,
-            safe1: safe1,
-            safe2: safe2,
-            safe3: safe3,
-            safe4: safe4,
-        },
-        opponent: AllZoneInfo {
-            desk: opponent_desk,
-            grave: opponent_grave,
-            lx: opponent_lx,
-            jq: opponent_jq,
-            battle1: opponent_battle1,
-            battle2: opponent_battle2,
-            battle3: opponent_battle3,
-            prepare: opponent_prepare,
-            safe1: opponent_safe1,
-            safe2: opponent_safe2,
-            safe3: opponent_safe3,
-            safe4: opponent_safe4,
-        },
-    });
+                opt_capacity: if is_field { None } else { zone.capacity },
+            });
+        }
+        if let Some(card_state) = zone.card_state() {
+            entity.insert(card_state);
+        }
+        if !zone.can_set_on.is_empty() {
+            entity.insert(CanSetOn(zone.can_set_on.clone()));
+        }
+        let Some(owner) = zone.owner() else {
+            continue;
+        };
+        entity.insert(owner);
+        if let Some(key) = zone.zone_type.key(zone.slot) {
+            if registry
+                .insert(owner, key, entity.id(), zone.rule())
+                .is_some()
+            {
+                warn!("zone {:?} of {:?} is defined twice", key, owner);
+            }
+        }
+    }
+    // 布局里缺少的场地
+    for player in PlayerId::ALL {
+        for key in [
+            ZoneKey::Desk,
+            ZoneKey::Grave,
+            ZoneKey::Lx,
+            ZoneKey::Jq,
+            ZoneKey::Prepare,
+        ] {
+            if registry.zone(player, key).is_none() {
+                warn!("zone {:?} of {:?} is missing in board layout", key, player);
+            }
+        }
+    }
+    registry
 }
 
-#[derive(Debug, Resource, Clone)]
-pub struct AllZoneInfoResource {
-    pub my: AllZoneInfo,
-    pub opponent: AllZoneInfo,
+// 场地注册表 按玩家 类型和编号查找场地
+#[derive(Resource, Clone, Debug, Default)]
+pub struct ZoneRegistry {
+    by_key: HashMap<(PlayerId, ZoneKey), Entity>,
+    by_entity: HashMap<Entity, (PlayerId, ZoneKey)>,
+    rules: HashMap<(PlayerId, ZoneKey), ZoneRule>,
 }
 
-#[derive(Debug, Clone)]
-pub struct AllZoneInfo {
-    pub desk: Entity,
-    pub grave: Entity,
-    pub lx: Entity,
-    pub jq: Entity,
-    pub battle1: Entity,
-    pub battle2: Entity,
-    pub battle3: Entity,
-    pub prepare: Entity,
-    pub safe1: Entity,
-    pub safe2: Entity,
-    pub safe3: Entity,
-    pub safe4: Entity,
+impl ZoneRegistry {
+    // 返回被替换的场地
+    pub fn insert(
+        &mut self,
+        player: PlayerId,
+        key: ZoneKey,
+        entity: Entity,
+        rule: ZoneRule,
+    ) -> Option<Entity> {
+        self.rules.insert((player, key), rule);
+        self.by_entity.insert(entity, (player, key));
+        let old = self.by_key.insert((player, key), entity);
+        if let Some(old) = old {
+            self.by_entity.remove(&old);
+        }
+        old
+    }
+
+    // 规则核心的区域对应的实体
+    pub fn zone(&self, player: PlayerId, key: ZoneKey) -> Option<Entity> {
+        self.by_key.get(&(player, key)).copied()
+    }
+
+    // 按类型和编号查找 只有一个的场地忽略编号
+    pub fn get(&self, player: PlayerId, zone_type: ZoneType, slot: usize) -> Option<Entity> {
+        zone_type
+            .key(Some(slot))
+            .and_then(|key| self.zone(player, key))
+    }
+
+    // 某个玩家某种类型的所有场地 按编号排序
+    pub fn iter(
+        &self,
+        player: PlayerId,
+        zone_type: ZoneType,
+    ) -> impl Iterator<Item = (ZoneKey, Entity)> {
+        let mut list: Vec<(ZoneKey, Entity)> = self
+            .by_key
+            .iter()
+            .filter(|((owner, key), _)| *owner == player && ZoneType::from(*key) == zone_type)
+            .map(|((_, key), entity)| (*key, *entity))
+            .collect();
+        list.sort_by_key(|(key, _)| key.slot());
+        list.into_iter()
+    }
+
+    // 某个玩家棋盘上的所有区域
+    pub fn keys(&self, player: PlayerId) -> Vec<ZoneKey> {
+        self.by_key
+            .keys()
+            .filter(|(owner, _)| *owner == player)
+            .map(|(_, key)| *key)
+            .collect()
+    }
+
+    // 某个玩家棋盘上的所有区域和区域的规则 交给规则核心
+    pub fn board(&self, player: PlayerId) -> Vec<(ZoneKey, ZoneRule)> {
+        self.keys(player)
+            .into_iter()
+            .map(|key| {
+                let rule = self.rules.get(&(player, key)).cloned().unwrap_or_default();
+                (key, rule)
+            })
+            .collect()
+    }
+
+    // 场地实体对应的玩家和区域
+    pub fn locate(&self, entity: Entity) -> Option<(PlayerId, ZoneKey)> {
+        self.by_entity.get(&entity).copied()
+    }
+
+    pub fn owner_and_type(&self, entity: Entity) -> Option<(PlayerId, ZoneType)> {
+        self.locate(entity)
+            .map(|(player, key)| (player, ZoneType::from(key)))
+    }
 }
@@ -0,0 +1,133 @@
+use crate::asset_dir::asset_path;
+use crate::card_info::card_enums::CardType;
+use crate::rules::{PlayerId, ZoneRule};
+use crate::zone_info::ZoneType;
+use bevy::prelude::*;
+use bevy_card3d_kit::prelude::card_state::CardState;
+use serde::Deserialize;
+use std::f32::consts::PI;
+
+/// 棋盘布局
+/// 场地的位置和属性从布局文件读取 可以用环境变量 BOARD_LAYOUT 指定其他布局文件
+/// 布局文件的相对路径相对 assets
+
+pub const DEFAULT_LAYOUT_PATH: &str = "layouts/default.ron";
+// 布局文件读取失败时使用的内置布局
+const DEFAULT_LAYOUT: &str = include_str!("../assets/layouts/default.ron");
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct BoardLayout {
+    // 一格的边长
+    pub cell: f32,
+    // 中间区域的宽度
+    pub mid: f32,
+    // 场地之间的间隙
+    pub gap: f32,
+    pub zones: Vec<ZoneLayout>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ZoneLayout {
+    pub name: String,
+    pub zone_type: ZoneType,
+    // 为空时不属于任何玩家
+    #[serde(default)]
+    pub owner: Option<u8>,
+    // 战场和安全屋的编号
+    #[serde(default)]
+    pub slot: Option<usize>,
+    // 以格子为单位的位置 row 为正在对手一侧
+    pub column: f32,
+    pub row: f32,
+    // 占用的格子数
+    #[serde(default = "default_span")]
+    pub span: (f32, f32),
+    // 直接指定大小 优先于 span
+    #[serde(default)]
+    pub size: Option<(f32, f32)>,
+    // 绕 Z 轴旋转的角度
+    #[serde(default)]
+    pub rotation: f32,
+    // 容量和可以登场的卡片类型同时是规则核心中区域的规则
+    #[serde(default)]
+    pub capacity: Option<usize>,
+    // 放入的卡片默认的状态
+    #[serde(default)]
+    pub card_state: Option<CardStateLayout>,
+    #[serde(default)]
+    pub can_set_on: Vec<CardType>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct CardStateLayout {
+    pub face_up: bool,
+    pub vertical: bool,
+}
+
+fn default_span() -> (f32, f32) {
+    (1.0, 1.0)
+}
+
+impl BoardLayout {
+    pub fn parse(text: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(text)
+    }
+
+    // 读取布局文件 失败时使用内置布局
+    pub fn load() -> Self {
+        let path = asset_path(
+            std::env::var("BOARD_LAYOUT").unwrap_or_else(|_| DEFAULT_LAYOUT_PATH.to_string()),
+        );
+        match std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| Self::parse(&text).map_err(|e| e.to_string()))
+        {
+            Ok(layout) => layout,
+            Err(err) => {
+                warn!("failed to load board layout {}: {}", path.display(), err);
+                Self::parse(DEFAULT_LAYOUT).expect("built-in board layout is invalid")
+            }
+        }
+    }
+
+    // 场地中心
+    pub fn transform(&self, zone: &ZoneLayout) -> Transform {
+        // 中间区域两侧各隔开半个 mid
+        let offset = if zone.row == 0.0 {
+            0.0
+        } else {
+            zone.row.signum() * self.mid * 0.5
+        };
+        let y = zone.row * self.cell + offset;
+        Transform::from_xyz(zone.column * self.cell, y, 0.0)
+            .with_rotation(Quat::from_axis_angle(Vec3::Z, zone.rotation * PI / 180.0))
+    }
+
+    // 场地大小
+    pub fn size(&self, zone: &ZoneLayout) -> Vec2 {
+        match zone.size {
+            Some((x, y)) => Vec2::new(x, y),
+            None => Vec2::new(zone.span.0, zone.span.1) * self.cell - self.gap,
+        }
+    }
+}
+
+impl ZoneLayout {
+    pub fn owner(&self) -> Option<PlayerId> {
+        self.owner.map(PlayerId)
+    }
+
+    pub fn rule(&self) -> ZoneRule {
+        ZoneRule {
+            capacity: self.capacity,
+            accepts: self.can_set_on.clone(),
+        }
+    }
+
+    pub fn card_state(&self) -> Option<CardState> {
+        self.card_state.map(|state| CardState {
+            face_up: state.face_up,
+            vertical: state.vertical,
+        })
+    }
+}